@@ -0,0 +1,4 @@
+// Migrations are embedded into the binary, so it has to be rebuilt whenever they change
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
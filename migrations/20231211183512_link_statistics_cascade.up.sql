@@ -0,0 +1,6 @@
+alter table link_statistics
+    drop constraint fk_links,
+    add constraint fk_links
+        foreign key (link_id)
+            references links (id)
+            on delete cascade;
@@ -0,0 +1,4 @@
+drop index if exists idx_links_created_at;
+
+alter table links
+    drop column if exists created_at;
@@ -0,0 +1,4 @@
+alter table links
+    add column if not exists created_at timestamptz not null default now();
+
+create index idx_links_created_at on links using btree (created_at);
@@ -0,0 +1,10 @@
+create table if not exists api_keys
+(
+    id         uuid        default gen_random_uuid() not null primary key,
+    name       text                                  not null,
+    key_hash   text                                  not null,
+    created_at timestamptz default now()             not null,
+    revoked_at timestamptz
+);
+
+create unique index idx_api_keys_key_hash on api_keys using btree (key_hash);
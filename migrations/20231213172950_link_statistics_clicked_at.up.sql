@@ -0,0 +1,4 @@
+alter table link_statistics
+    add column if not exists clicked_at timestamptz not null default now();
+
+create index idx_link_statistics_link_id_clicked_at on link_statistics using btree (link_id, clicked_at);
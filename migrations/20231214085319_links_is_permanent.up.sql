@@ -0,0 +1,2 @@
+alter table links
+    add column if not exists is_permanent boolean not null default false;
@@ -0,0 +1,2 @@
+alter table links
+    add column if not exists max_clicks bigint;
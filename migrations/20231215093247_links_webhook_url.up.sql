@@ -0,0 +1,2 @@
+alter table links
+    add column if not exists webhook_url text;
@@ -0,0 +1,2 @@
+alter table link_statistics
+    add column if not exists locale varchar(64) default null;
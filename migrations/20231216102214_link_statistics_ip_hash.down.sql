@@ -0,0 +1,2 @@
+alter table link_statistics
+    drop column if exists ip_hash;
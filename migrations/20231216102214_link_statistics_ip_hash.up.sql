@@ -0,0 +1,2 @@
+alter table link_statistics
+    add column if not exists ip_hash varchar(64) default null;
@@ -0,0 +1,2 @@
+alter table links
+    add column if not exists updated_at timestamptz not null default now();
@@ -0,0 +1,3 @@
+alter table links
+    drop column if exists title,
+    drop column if exists description;
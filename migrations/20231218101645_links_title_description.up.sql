@@ -0,0 +1,3 @@
+alter table links
+    add column if not exists title varchar(255) default null,
+    add column if not exists description text default null;
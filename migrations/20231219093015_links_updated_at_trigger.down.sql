@@ -0,0 +1,3 @@
+drop trigger if exists links_set_updated_at on links;
+
+drop function if exists set_updated_at();
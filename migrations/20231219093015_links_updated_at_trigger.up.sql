@@ -0,0 +1,11 @@
+create or replace function set_updated_at() returns trigger as $$
+begin
+    new.updated_at = now();
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger links_set_updated_at
+    before update on links
+    for each row
+    execute function set_updated_at();
@@ -0,0 +1,2 @@
+alter table links
+    add column if not exists signed boolean not null default false;
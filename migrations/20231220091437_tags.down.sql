@@ -0,0 +1,5 @@
+drop function if exists link_tag_names(text);
+
+drop table if exists link_tags;
+
+drop table if exists tags;
@@ -0,0 +1,29 @@
+create table if not exists tags
+(
+    id   uuid default gen_random_uuid() not null primary key,
+    name text                           not null unique
+);
+
+create table if not exists link_tags
+(
+    link_id text not null,
+    tag_id  uuid not null,
+    primary key (link_id, tag_id),
+    constraint fk_links
+        foreign key (link_id)
+            references links (id)
+            on delete cascade,
+    constraint fk_tags
+        foreign key (tag_id)
+            references tags (id)
+            on delete cascade
+);
+
+create index idx_link_tags_tag_id on link_tags using btree (tag_id);
+
+create or replace function link_tag_names(link_id text) returns text[] as $$
+    select coalesce(array_agg(tags.name order by tags.name), '{}')
+    from link_tags
+    join tags on tags.id = link_tags.tag_id
+    where link_tags.link_id = link_tag_names.link_id;
+$$ language sql stable;
@@ -0,0 +1,12 @@
+create table if not exists idempotency_keys
+(
+    key        uuid                      not null,
+    link_id    text                      not null,
+    created_at timestamptz default now() not null,
+    constraint fk_links
+        foreign key (link_id)
+            references links (id)
+            on delete cascade
+);
+
+create unique index idx_idempotency_keys_key on idempotency_keys using btree (key);
@@ -0,0 +1,2 @@
+alter table api_keys
+    add column if not exists grace_period_ends_at timestamptz;
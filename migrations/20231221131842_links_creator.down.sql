@@ -0,0 +1,4 @@
+alter table links
+    drop constraint if exists fk_api_keys,
+    drop column if exists created_by_key_id,
+    drop column if exists creator_user_agent;
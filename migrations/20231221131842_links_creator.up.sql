@@ -0,0 +1,7 @@
+alter table links
+    add column if not exists creator_user_agent varchar(512),
+    add column if not exists created_by_key_id  uuid,
+    add constraint fk_api_keys
+        foreign key (created_by_key_id)
+            references api_keys (id)
+            on delete set null;
@@ -0,0 +1,3 @@
+alter table link_statistics
+    drop column if exists os,
+    drop column if exists browser;
@@ -0,0 +1,3 @@
+alter table link_statistics
+    add column if not exists browser varchar(64) default null,
+    add column if not exists os      varchar(64) default null;
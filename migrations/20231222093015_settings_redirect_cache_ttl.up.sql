@@ -0,0 +1,2 @@
+alter table settings
+    add column if not exists redirect_cache_ttl_secs integer;
@@ -0,0 +1,5 @@
+drop index if exists idx_links_canonical_id;
+
+alter table links
+    drop constraint if exists fk_canonical_links,
+    drop column if exists canonical_id;
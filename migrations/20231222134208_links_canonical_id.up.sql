@@ -0,0 +1,8 @@
+alter table links
+    add column if not exists canonical_id text,
+    add constraint fk_canonical_links
+        foreign key (canonical_id)
+            references links (id)
+            on delete set null;
+
+create index idx_links_canonical_id on links using btree (canonical_id);
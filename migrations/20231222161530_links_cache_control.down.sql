@@ -0,0 +1,2 @@
+alter table links
+    drop column if exists cache_control;
@@ -0,0 +1,2 @@
+alter table links
+    add column if not exists cache_control varchar(255) default null;
@@ -0,0 +1,5 @@
+drop trigger if exists links_audit on links;
+
+drop function if exists audit_link_change();
+
+drop table if exists audit_log;
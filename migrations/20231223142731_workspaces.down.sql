@@ -0,0 +1,13 @@
+alter table api_keys
+    drop constraint if exists fk_api_keys_workspaces,
+    drop column if exists workspace_id;
+
+drop index if exists idx_links_workspace_id;
+
+alter table links
+    drop constraint if exists fk_links_workspaces,
+    drop column if exists workspace_id;
+
+drop index if exists idx_workspaces_name;
+
+drop table if exists workspaces;
@@ -0,0 +1,22 @@
+create table if not exists workspaces
+(
+    id         uuid        default gen_random_uuid() not null primary key,
+    name       text                                  not null,
+    created_at timestamptz default now()             not null
+);
+
+create unique index idx_workspaces_name on workspaces using btree (name);
+
+alter table links
+    add column if not exists workspace_id uuid,
+    add constraint fk_links_workspaces
+        foreign key (workspace_id)
+            references workspaces (id);
+
+create index idx_links_workspace_id on links using btree (workspace_id);
+
+alter table api_keys
+    add column if not exists workspace_id uuid,
+    add constraint fk_api_keys_workspaces
+        foreign key (workspace_id)
+            references workspaces (id);
@@ -0,0 +1,3 @@
+alter table links
+    drop column if exists preview_fetched_at,
+    drop column if exists preview_metadata;
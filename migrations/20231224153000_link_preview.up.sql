@@ -0,0 +1,3 @@
+alter table links
+    add column if not exists preview_metadata jsonb,
+    add column if not exists preview_fetched_at timestamptz;
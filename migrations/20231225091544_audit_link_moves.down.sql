@@ -0,0 +1,34 @@
+-- Handlers tell who acted by setting link_shortener.actor_key_id for their transaction.
+-- Changes made by the service itself, like purging expired links, have no actor.
+create or replace function audit_link_change() returns trigger as $$
+declare
+    action text;
+begin
+    if tg_op = 'INSERT' then
+        action = 'create';
+    elsif tg_op = 'DELETE' then
+        action = 'purge';
+    elsif old.deleted_at is null and new.deleted_at is not null then
+        action = 'delete';
+    elsif old.deleted_at is not null and new.deleted_at is null then
+        action = 'restore';
+    elsif old.active and not new.active then
+        action = 'deactivate';
+    elsif not old.active and new.active then
+        action = 'activate';
+    else
+        action = 'update';
+    end if;
+
+    insert into audit_log (link_id, action, actor_key_id, old_value, new_value)
+    values (
+        coalesce(new.id, old.id),
+        action,
+        nullif(current_setting('link_shortener.actor_key_id', true), '')::uuid,
+        case when tg_op = 'INSERT' then null else to_jsonb(old) end,
+        case when tg_op = 'DELETE' then null else to_jsonb(new) end
+    );
+
+    return null;
+end;
+$$ language plpgsql;
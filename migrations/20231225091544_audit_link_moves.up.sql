@@ -0,0 +1,45 @@
+-- Moving links between workspaces only records the workspaces, the rest of the link stays the same.
+create or replace function audit_link_change() returns trigger as $$
+declare
+    action text;
+    old_value jsonb;
+    new_value jsonb;
+begin
+    if tg_op = 'INSERT' then
+        action = 'create';
+    elsif tg_op = 'DELETE' then
+        action = 'purge';
+    elsif old.deleted_at is null and new.deleted_at is not null then
+        action = 'delete';
+    elsif old.deleted_at is not null and new.deleted_at is null then
+        action = 'restore';
+    elsif old.active and not new.active then
+        action = 'deactivate';
+    elsif not old.active and new.active then
+        action = 'activate';
+    elsif old.workspace_id is distinct from new.workspace_id then
+        action = 'move';
+    else
+        action = 'update';
+    end if;
+
+    if action = 'move' then
+        old_value = jsonb_build_object('workspaceId', old.workspace_id);
+        new_value = jsonb_build_object('workspaceId', new.workspace_id);
+    else
+        old_value = case when tg_op = 'INSERT' then null else to_jsonb(old) end;
+        new_value = case when tg_op = 'DELETE' then null else to_jsonb(new) end;
+    end if;
+
+    insert into audit_log (link_id, action, actor_key_id, old_value, new_value)
+    values (
+        coalesce(new.id, old.id),
+        action,
+        nullif(current_setting('link_shortener.actor_key_id', true), '')::uuid,
+        old_value,
+        new_value
+    );
+
+    return null;
+end;
+$$ language plpgsql;
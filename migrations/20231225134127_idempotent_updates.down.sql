@@ -0,0 +1,9 @@
+delete from idempotency_keys where method <> 'POST';
+
+drop index if exists idx_idempotency_keys_key_method;
+
+create unique index if not exists idx_idempotency_keys_key on idempotency_keys using btree (key);
+
+alter table idempotency_keys
+    drop column if exists response_body,
+    drop column if exists method;
@@ -0,0 +1,8 @@
+alter table idempotency_keys
+    add column if not exists method       text default 'POST' not null,
+    add column if not exists response_body jsonb;
+
+-- The same key may be used for a create and an update, each of them is replayed on its own
+drop index if exists idx_idempotency_keys_key;
+
+create unique index if not exists idx_idempotency_keys_key_method on idempotency_keys using btree (key, method);
@@ -0,0 +1,12 @@
+-- Only one workspace can keep a key that several of them used
+delete from idempotency_keys duplicate
+using idempotency_keys kept
+where duplicate.key = kept.key and duplicate.method = kept.method and duplicate.ctid > kept.ctid;
+
+drop index if exists idx_idempotency_keys_key_method_workspace;
+
+create unique index if not exists idx_idempotency_keys_key_method on idempotency_keys using btree (key, method);
+
+alter table idempotency_keys
+    drop constraint if exists fk_idempotency_keys_workspaces,
+    drop column if exists workspace_id;
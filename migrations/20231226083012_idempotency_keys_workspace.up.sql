@@ -0,0 +1,18 @@
+-- Keys are scoped by the workspace that used them, so that a key reused by another
+-- workspace never replays a link the other workspace cannot see
+alter table idempotency_keys
+    add column if not exists workspace_id uuid,
+    add constraint fk_idempotency_keys_workspaces
+        foreign key (workspace_id)
+            references workspaces (id)
+            on delete cascade;
+
+update idempotency_keys
+set workspace_id = links.workspace_id
+from links
+where links.id = idempotency_keys.link_id;
+
+drop index if exists idx_idempotency_keys_key_method;
+
+create unique index if not exists idx_idempotency_keys_key_method_workspace
+    on idempotency_keys using btree (key, method, workspace_id) nulls not distinct;
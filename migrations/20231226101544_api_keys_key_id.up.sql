@@ -0,0 +1,7 @@
+-- Keys issued from now on start with their id, those issued before cannot be told
+-- apart by their hash and are still looked up by trying each of them
+alter table api_keys
+    add column if not exists key_has_id boolean default false not null;
+
+alter table api_keys
+    alter column key_has_id set default true;
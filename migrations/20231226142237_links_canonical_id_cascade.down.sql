@@ -0,0 +1,6 @@
+alter table links
+    drop constraint if exists fk_canonical_links,
+    add constraint fk_canonical_links
+        foreign key (canonical_id)
+            references links (id)
+            on delete set null;
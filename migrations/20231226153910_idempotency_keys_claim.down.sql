@@ -0,0 +1,4 @@
+delete from idempotency_keys where link_id is null;
+
+alter table idempotency_keys
+    alter column link_id set not null;
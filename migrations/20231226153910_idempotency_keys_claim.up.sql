@@ -0,0 +1,4 @@
+-- Creates claim their key before the link exists, the link is only set once it is
+-- inserted within the same transaction
+alter table idempotency_keys
+    alter column link_id drop not null;
@@ -0,0 +1,62 @@
+-- Fetching a preview is no change of the link, so it keeps its updated_at, which its
+-- ETag and Last-Modified are based on, and records no audit event
+drop trigger if exists links_set_updated_at on links;
+
+create trigger links_set_updated_at
+    before update on links
+    for each row
+    when (not (
+        to_jsonb(old) - '{preview_metadata,preview_fetched_at}'::text[]
+            = to_jsonb(new) - '{preview_metadata,preview_fetched_at}'::text[]
+        and (old.preview_metadata, old.preview_fetched_at) is distinct from (new.preview_metadata, new.preview_fetched_at)
+    ))
+    execute function set_updated_at();
+
+create or replace function audit_link_change() returns trigger as $$
+declare
+    action text;
+    old_value jsonb;
+    new_value jsonb;
+begin
+    if tg_op = 'INSERT' then
+        action = 'create';
+    elsif tg_op = 'DELETE' then
+        action = 'purge';
+    elsif to_jsonb(old) - '{preview_metadata,preview_fetched_at}'::text[]
+            = to_jsonb(new) - '{preview_metadata,preview_fetched_at}'::text[]
+        and (old.preview_metadata, old.preview_fetched_at) is distinct from (new.preview_metadata, new.preview_fetched_at) then
+        return null;
+    elsif old.deleted_at is null and new.deleted_at is not null then
+        action = 'delete';
+    elsif old.deleted_at is not null and new.deleted_at is null then
+        action = 'restore';
+    elsif old.active and not new.active then
+        action = 'deactivate';
+    elsif not old.active and new.active then
+        action = 'activate';
+    elsif old.workspace_id is distinct from new.workspace_id then
+        action = 'move';
+    else
+        action = 'update';
+    end if;
+
+    if action = 'move' then
+        old_value = jsonb_build_object('workspaceId', old.workspace_id);
+        new_value = jsonb_build_object('workspaceId', new.workspace_id);
+    else
+        old_value = case when tg_op = 'INSERT' then null else to_jsonb(old) end;
+        new_value = case when tg_op = 'DELETE' then null else to_jsonb(new) end;
+    end if;
+
+    insert into audit_log (link_id, action, actor_key_id, old_value, new_value)
+    values (
+        coalesce(new.id, old.id),
+        action,
+        nullif(current_setting('link_shortener.actor_key_id', true), '')::uuid,
+        old_value,
+        new_value
+    );
+
+    return null;
+end;
+$$ language plpgsql;
@@ -0,0 +1,1527 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::multipart::MultipartError;
+use axum::extract::Multipart;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use base64::Engine;
+use base64::engine::general_purpose;
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use metrics::{counter, increment_counter};
+use rand::RngCore;
+use sqlx::{Error, PgPool};
+use sqlx::error::ErrorKind;
+use tokio_util::codec::Encoder;
+use uuid::Uuid;
+
+use crate::audit;
+use crate::auth::{format_api_key, hashed_api_key, GLOBAL_API_KEY_NAME, SETTINGS_WRITE_LOCK};
+use crate::config::Config;
+use crate::error::{AppError, ErrorBody};
+use crate::routes::{custom_id_regex, invalidate_cached_link, parse_target_url, Link, PaginatedLinks, Pagination, UtmParams};
+use crate::state::{AppState, LinkCacheTtl};
+use crate::utils::{internal_error, timed_query};
+
+const DEFAULT_TOP_LINKS_LIMIT: i64 = 10;
+
+const MAX_TOP_LINKS_LIMIT: i64 = 100;
+
+const IMPORT_HEADER: [&str; 2] = ["id", "target_url"];
+
+const RAW_STATISTICS_BUFFERED_ROWS: usize = 64;
+
+const EXPORT_HEADER: [&str; 4] = ["id", "target_url", "created_at", "clicks"];
+
+const EXPORT_BUFFERED_ROWS: usize = 64;
+
+const GRACE_PERIOD_HEADER: &str = "x-grace-period-secs";
+
+const DEFAULT_GRACE_PERIOD_SECS: u32 = 3600;
+
+const MAX_REDIRECT_CACHE_TTL_SECS: u64 = 86400;
+
+const MAX_BULK_DELETE_IDS: usize = 500;
+
+const PRUNE_BATCH_SIZE: i64 = 500;
+
+/// Has to be sent as `yes` with requests that delete links for good in bulk
+const CONFIRM_DESTRUCTIVE_HEADER: &str = "confirm-destructive";
+
+const DEFAULT_AUDIT_EVENTS_LIMIT: i64 = 50;
+
+const MAX_AUDIT_EVENTS_LIMIT: i64 = 500;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    /// Keys without a workspace belong to the default workspace of the global key
+    pub workspace_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Only set while the key is being rotated, it stops working at this point
+    pub grace_period_ends_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewApiKey {
+    pub name: String,
+    /// The key only sees links of this workspace, defaults to the one of the global key
+    pub workspace_id: Option<Uuid>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWorkspace {
+    pub name: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsUpdate {
+    /// Rotates the global api key
+    pub new_api_key: Option<String>,
+    /// Applies to links cached from now on, without a restart
+    pub redirect_cache_ttl_secs: Option<u64>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsUpdated {
+    pub rotated: bool,
+    pub redirect_cache_ttl_secs: u64,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TopLink {
+    pub id: String,
+    pub target_url: String,
+    pub clicks: i64,
+}
+
+/// A single click, exported as one line of newline delimited json
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RawLinkStatistic {
+    pub id: i32,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub locale: Option<String>,
+    pub ip_hash: Option<String>,
+    pub browser: Option<String>,
+    pub os: Option<String>,
+    pub clicked_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TopLinksQuery {
+    pub limit: Option<i64>,
+    /// Only the links of this workspace, those of the default workspace when left out
+    pub workspace_id: Option<Uuid>,
+}
+
+/// A change of a link, recorded by the database within the transaction that made it
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub link_id: String,
+    /// One of create, update, delete, restore, activate, deactivate, purge, move, or clear_statistics
+    pub action: String,
+    /// Id of the api key that made the change, empty for the global key and the service itself
+    pub actor_key_id: Option<Uuid>,
+    /// The link before the change, empty for creations. Moves only record the workspace id.
+    #[schema(value_type = Option<Object>)]
+    pub old_value: Option<serde_json::Value>,
+    /// The link after the change, empty for purges
+    #[schema(value_type = Option<Object>)]
+    pub new_value: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub link_id: Option<String>,
+    /// Only events at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only events before this time. Passing the time of the oldest event returned
+    /// together with its id as `before_id` fetches the next page.
+    pub to: Option<DateTime<Utc>>,
+    /// Also the events at the time of `to` that are listed after the event with this
+    /// id, as the events of a transaction share their time
+    pub before_id: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportLinksQuery {
+    /// Also exports soft deleted links
+    pub include_deleted: Option<bool>,
+    /// Only the links of this workspace, those of the default workspace when left out
+    pub workspace_id: Option<Uuid>,
+}
+
+/// A single row of the csv export
+pub struct LinkExport {
+    pub id: String,
+    pub target_url: String,
+    pub created_at: DateTime<Utc>,
+    pub clicks: i64,
+}
+
+/// Encodes links as csv rows, so that each of them can be sent as soon as it was read
+pub struct LinkExportCodec;
+
+impl LinkExportCodec {
+    fn write_record<I, T>(record: I, dst: &mut BytesMut) -> Result<(), csv::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(dst.writer());
+
+        writer.write_record(record)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn encode_header(&mut self, dst: &mut BytesMut) -> Result<(), csv::Error> {
+        Self::write_record(EXPORT_HEADER, dst)
+    }
+}
+
+impl Encoder<LinkExport> for LinkExportCodec {
+    type Error = csv::Error;
+
+    fn encode(&mut self, link: LinkExport, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        Self::write_record(
+            [
+                link.id,
+                link.target_url,
+                link.created_at.to_rfc3339(),
+                link.clicks.to_string(),
+            ],
+            dst,
+        )
+    }
+}
+
+/// A csv file with the header row `id,target_url`
+#[derive(utoipa::ToSchema)]
+#[allow(dead_code)]
+pub struct LinkImportFile {
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkImportError {
+    /// The line of the csv file, the header row being line 1
+    pub row: u32,
+    pub reason: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkImportSummary {
+    pub imported: u32,
+    pub skipped: u32,
+    pub errors: Vec<LinkImportError>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkDeleteRequest {
+    pub ids: Vec<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkDeleteSummary {
+    pub deleted: u32,
+    /// Ids that do not exist or were already deleted
+    pub not_found: Vec<String>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveLinksRequest {
+    pub link_ids: Vec<String>,
+    /// Empty moves the links to the default workspace
+    pub target_workspace_id: Option<Uuid>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveLinksSummary {
+    pub moved: u32,
+    /// Ids that do not exist
+    pub not_found: Vec<String>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PruneLinksQuery {
+    /// Links created before this time are deleted
+    pub older_than: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinksPruned {
+    pub deleted: i64,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StatisticsCleared {
+    /// Number of clicks that were removed
+    pub deleted: i64,
+}
+
+fn generate_api_key(id: Uuid) -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+
+    format_api_key(id, &general_purpose::URL_SAFE_NO_PAD.encode(secret))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys",
+    request_body = NewApiKey,
+    responses(
+        (status = 201, description = "Created api key, the key itself is only returned once", body = CreatedApiKey),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Workspace not found")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn create_api_key(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Json(new_api_key): Json<NewApiKey>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), AppError> {
+    let id = Uuid::new_v4();
+    let key = generate_api_key(id);
+    let key_hash = hashed_api_key(&key, config.key_algorithm).await?;
+
+    let insert_api_key_timeout = config.db_query_timeout;
+
+    let api_key = timed_query(
+        "insert_api_key",
+        insert_api_key_timeout,
+        sqlx::query_as!(
+            ApiKey,
+            r#"
+            insert into api_keys(id, name, key_hash, workspace_id)
+            values ($4, $1, $2, $3)
+            returning id, name, workspace_id, created_at, revoked_at, grace_period_ends_at
+            "#,
+            &new_api_key.name,
+            key_hash,
+            new_api_key.workspace_id,
+            id
+        )
+        .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(|err| match err {
+        Error::Database(db_err) if db_err.kind() == ErrorKind::ForeignKeyViolation => {
+            AppError::UnprocessableEntity(ErrorBody::new("workspace_not_found", "workspace not found"))
+        }
+        err => internal_error(err),
+    })?;
+
+    tracing::debug!("Created new api key with id {} named {}", api_key.id, api_key.name);
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { api_key, key })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    responses((status = 200, description = "All api keys", body = Vec<ApiKey>)),
+    security(("api_key" = []))
+)]
+pub async fn list_api_keys(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+) -> Result<Json<Vec<ApiKey>>, AppError> {
+    let fetch_api_keys_timeout = config.db_query_timeout;
+
+    let api_keys = timed_query(
+        "select_api_keys",
+        fetch_api_keys_timeout,
+        sqlx::query_as!(
+            ApiKey,
+            "select id, name, workspace_id, created_at, revoked_at, grace_period_ends_at from api_keys order by created_at"
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Listed {} api keys", api_keys.len());
+
+    Ok(Json(api_keys))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/keys/{id}",
+    params(("id" = Uuid, Path, description = "Id of the api key")),
+    responses(
+        (status = 204, description = "Api key revoked"),
+        (status = 404, description = "Api key not found or already revoked")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn revoke_api_key(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Path(api_key_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let revoke_api_key_timeout = config.db_query_timeout;
+
+    let revoked_api_key = timed_query(
+        "revoke_api_key",
+        revoke_api_key_timeout,
+        sqlx::query!(
+            "update api_keys set revoked_at = now() where id = $1 and revoked_at is null",
+            api_key_id
+        )
+        .execute(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    if revoked_api_key.rows_affected() == 0 {
+        return Err(AppError::NotFound(ErrorBody::new("api_key_not_found", "Not found")));
+    }
+
+    tracing::debug!("Revoked api key with id {}", api_key_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys/{id}/rotate",
+    params(
+        ("id" = Uuid, Path, description = "Id of the api key"),
+        ("X-Grace-Period-Secs" = Option<u32>, Header, description = "How long the old key keeps working, defaults to an hour")
+    ),
+    responses(
+        (status = 201, description = "Created replacement api key, the key itself is only returned once", body = CreatedApiKey),
+        (status = 404, description = "Api key not found or already revoked"),
+        (status = 409, description = "Api key is already being rotated"),
+        (status = 422, description = "Grace period invalid")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn rotate_api_key(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Path(api_key_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<CreatedApiKey>), AppError> {
+    let grace_period_secs = match headers.get(GRACE_PERIOD_HEADER) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .ok_or_else(|| {
+                AppError::UnprocessableEntity(ErrorBody::new(
+                    "invalid_grace_period",
+                    "grace period must be a number of seconds",
+                ))
+            })?,
+        None => DEFAULT_GRACE_PERIOD_SECS,
+    };
+
+    let id = Uuid::new_v4();
+    let key = generate_api_key(id);
+    let key_hash = hashed_api_key(&key, config.key_algorithm).await?;
+
+    let rotate_api_key_timeout = config.db_query_timeout;
+
+    // The replacement is only created if the old key could be marked as rotating, which
+    // keeps concurrent rotations of the same key from creating several replacements
+    let api_key = timed_query(
+        "rotate_api_key",
+        rotate_api_key_timeout,
+        sqlx::query_as!(
+            ApiKey,
+            r#"
+            with rotated_api_key as (
+                update api_keys set grace_period_ends_at = now() + make_interval(secs => $2)
+                where id = $1 and revoked_at is null and grace_period_ends_at is null
+                returning name, workspace_id
+            )
+            insert into api_keys(id, name, key_hash, workspace_id)
+            select $4, name, $3, workspace_id from rotated_api_key
+            returning id, name, workspace_id, created_at, revoked_at, grace_period_ends_at
+            "#,
+            api_key_id,
+            f64::from(grace_period_secs),
+            key_hash,
+            id
+        )
+        .fetch_optional(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let Some(api_key) = api_key else {
+        let is_rotating = timed_query(
+            "select_api_key_exists",
+            rotate_api_key_timeout,
+            sqlx::query_scalar!(
+                r#"select exists(select 1 from api_keys where id = $1 and revoked_at is null) as "exists!""#,
+                api_key_id
+            )
+            .fetch_one(&pool)
+        )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+
+        if is_rotating {
+            return Err(AppError::Conflict(ErrorBody::new("api_key_rotating", "api key is already being rotated")));
+        }
+
+        return Err(AppError::NotFound(ErrorBody::new("api_key_not_found", "Not found")));
+    };
+
+    tracing::info!(
+        event = "key_rotation_started",
+        "Started rotating api key with id {} to api key with id {}, the old key works for another {} seconds",
+        api_key_id,
+        api_key.id,
+        grace_period_secs
+    );
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { api_key, key })))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/admin/settings",
+    request_body = SettingsUpdate,
+    responses(
+        (status = 200, description = "Settings updated", body = SettingsUpdated),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "No setting given, new api key is empty, or redirect cache ttl out of range")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn update_settings(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    State(link_cache_ttl): State<LinkCacheTtl>,
+    Json(settings_update): Json<SettingsUpdate>,
+) -> Result<Json<SettingsUpdated>, AppError> {
+    if settings_update.new_api_key.is_none() && settings_update.redirect_cache_ttl_secs.is_none() {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new("settings_empty", "no setting to update was given")));
+    }
+
+    if settings_update.new_api_key.as_deref().is_some_and(str::is_empty) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new("api_key_empty", "new api key must not be empty")));
+    }
+
+    // Redis refuses to store keys with a ttl of zero
+    if settings_update
+        .redirect_cache_ttl_secs
+        .is_some_and(|ttl_secs| !(1..=MAX_REDIRECT_CACHE_TTL_SECS).contains(&ttl_secs))
+    {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "invalid_redirect_cache_ttl",
+            format!("redirect cache ttl must be between 1 and {} seconds", MAX_REDIRECT_CACHE_TTL_SECS),
+        )));
+    }
+
+    let new_api_key_hash = match settings_update.new_api_key.as_deref() {
+        Some(new_api_key) => Some(hashed_api_key(new_api_key, config.key_algorithm).await?),
+        None => None,
+    };
+
+    let update_settings_timeout = config.db_query_timeout;
+
+    let mut transaction = pool.begin().await.map_err(internal_error)?;
+
+    timed_query(
+        "lock_settings",
+        update_settings_timeout,
+        sqlx::query(SETTINGS_WRITE_LOCK).execute(&mut *transaction)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    // The ttl is stored as well, so that it survives restarts. Other instances only pick
+    // it up once they restart.
+    timed_query(
+        "update_settings",
+        update_settings_timeout,
+        sqlx::query!(
+            r#"
+            update settings
+            set encrypted_global_api_key = coalesce($1, encrypted_global_api_key),
+                redirect_cache_ttl_secs = coalesce($2, redirect_cache_ttl_secs)
+            where id = $3
+            "#,
+            new_api_key_hash,
+            settings_update.redirect_cache_ttl_secs.map(|ttl_secs| ttl_secs as i32),
+            "DEFAULT_SETTINGS"
+        )
+        .execute(&mut *transaction)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    let rotated = settings_update.new_api_key.is_some();
+
+    if rotated {
+        tracing::warn!("Rotated the global api key");
+    }
+
+    if let Some(ttl_secs) = settings_update.redirect_cache_ttl_secs {
+        link_cache_ttl.set(Duration::from_secs(ttl_secs));
+
+        tracing::info!("Changed the redirect cache ttl to {} seconds", ttl_secs);
+    }
+
+    Ok(Json(SettingsUpdated {
+        rotated,
+        redirect_cache_ttl_secs: link_cache_ttl.get().as_secs(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/links/deleted",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Page of deleted links, most recently deleted first", body = PaginatedLinks),
+        (status = 422, description = "Invalid pagination")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_deleted_links(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<PaginatedLinks>, AppError> {
+    let (page, page_size) = pagination.resolve()?;
+    let offset = i64::from(page - 1) * i64::from(page_size);
+
+    let fetch_links_timeout = config.db_query_timeout;
+
+    let links = timed_query(
+        "select_deleted_links",
+        fetch_links_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, link_tag_names(id) as "tags!" from links
+            where deleted_at is not null
+            order by deleted_at desc, id
+            limit $1 offset $2
+            "#,
+            i64::from(page_size),
+            offset
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let count_links_timeout = config.db_query_timeout;
+
+    let total_count = timed_query(
+        "count_deleted_links",
+        count_links_timeout,
+        sqlx::query_scalar!(r#"select count(*) as "total_count!" from links where deleted_at is not null"#)
+            .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Listed page {} of deleted links with page size {}", page, page_size);
+
+    Ok(Json(PaginatedLinks {
+        items: links,
+        page,
+        page_size,
+        total_count,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/links/{id}/raw-statistics",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Every click of the link in the order they happened, one per line", body = RawLinkStatistic, content_type = "application/x-ndjson"),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn get_raw_link_statistics(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Path(link_id): Path<String>,
+) -> Result<Response, AppError> {
+    let select_timeout = config.db_query_timeout;
+
+    // Statistics of deleted links are kept, so they can still be exported
+    let link_exists = timed_query(
+        "select_link_exists",
+        select_timeout,
+        sqlx::query_scalar!(r#"select exists(select 1 from links where id = $1) as "exists!""#, &link_id)
+            .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    if !link_exists {
+        return Err(AppError::NotFound(ErrorBody::new("link_not_found", "Not found")));
+    }
+
+    // The rows are streamed from a separate task, as the stream of a query borrows the pool.
+    // The bounded channel keeps a slow client from making the task buffer the whole result,
+    // and the query timeout does not apply, as large exports take as long as they take.
+    let (mut sender, receiver) = mpsc::channel::<Result<Vec<u8>, sqlx::Error>>(RAW_STATISTICS_BUFFERED_ROWS);
+
+    tokio::spawn(async move {
+        let mut statistics = sqlx::query_as!(
+            RawLinkStatistic,
+            r#"
+            select id, referer, user_agent, locale, ip_hash, browser, os, clicked_at from link_statistics
+            where link_id = $1
+            order by id
+            "#,
+            &link_id
+        )
+        .fetch(&pool);
+
+        let mut exported_rows = 0;
+
+        while let Some(statistic) = statistics.next().await {
+            let line = statistic.map(|statistic| {
+                let mut line = serde_json::to_vec(&statistic).expect("Serializing a statistic should never fail");
+                line.push(b'\n');
+
+                line
+            });
+
+            if let Err(err) = &line {
+                tracing::error!("Exporting the statistics of link with id {} failed: {}", link_id, err);
+            }
+
+            let failed = line.is_err();
+
+            // Sending only fails once the client went away, which also ends the query
+            if sender.send(line).await.is_err() || failed {
+                return;
+            }
+
+            exported_rows += 1;
+        }
+
+        tracing::debug!("Exported {} raw statistics of link with id {}", exported_rows, link_id);
+    });
+
+    Ok(([(CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(receiver)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/links/{id}/statistics/clear",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Every click of the link was removed, the link itself is kept", body = StatisticsCleared),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn clear_link_statistics(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Path(link_id): Path<String>,
+) -> Result<Json<StatisticsCleared>, AppError> {
+    let clear_statistics_timeout = config.db_query_timeout;
+
+    // Deleted links still have statistics, so they can be cleared as well
+    let link_exists = timed_query(
+        "select_link_exists",
+        clear_statistics_timeout,
+        sqlx::query_scalar!(r#"select exists(select 1 from links where id = $1) as "exists!""#, &link_id)
+            .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    if !link_exists {
+        return Err(AppError::NotFound(ErrorBody::new("link_not_found", "Not found")));
+    }
+
+    // The trigger only records changes of links, so the audit event is written alongside the
+    // deletion. Admin calls are made with the global key, which leaves the actor empty.
+    let deleted = timed_query(
+        "clear_statistics",
+        clear_statistics_timeout,
+        sqlx::query_scalar!(
+            r#"
+            with deleted_statistics as (
+                delete from link_statistics where link_id = $1 returning id
+            ), audit_event as (
+                insert into audit_log(link_id, action, old_value)
+                select $1, 'clear_statistics', jsonb_build_object('clicks', count(*)) from deleted_statistics
+            )
+            select count(*) as "deleted!" from deleted_statistics
+            "#,
+            &link_id
+        )
+        .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    increment_counter!("statistics_cleared_total");
+
+    tracing::info!("Cleared {} clicks of link with id {}", deleted, link_id);
+
+    Ok(Json(StatisticsCleared { deleted }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/links/export",
+    params(ExportLinksQuery),
+    responses(
+        (status = 200, description = "Every link with its number of clicks as csv with the header row `id,target_url,created_at,clicks`", body = String, content_type = "text/csv")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn export_links(
+    State(pool): State<PgPool>,
+    Query(query): Query<ExportLinksQuery>,
+) -> Response {
+    let include_deleted = query.include_deleted.unwrap_or(false);
+
+    // Streamed like the raw statistics, each row being encoded as soon as it arrives
+    let (mut sender, receiver) = mpsc::channel::<Result<Bytes, BoxError>>(EXPORT_BUFFERED_ROWS);
+
+    tokio::spawn(async move {
+        let mut codec = LinkExportCodec;
+        let mut buffer = BytesMut::new();
+
+        codec
+            .encode_header(&mut buffer)
+            .expect("Encoding the header row should never fail");
+
+        if sender.send(Ok(buffer.split().freeze())).await.is_err() {
+            return;
+        }
+
+        let mut links = sqlx::query_as!(
+            LinkExport,
+            r#"
+            select links.id, links.target_url, links.created_at,
+                (select count(*) from link_statistics where link_statistics.link_id = links.id) as "clicks!"
+            from links
+            where ($1 or links.deleted_at is null) and links.workspace_id is not distinct from $2
+            order by links.created_at, links.id
+            "#,
+            include_deleted,
+            query.workspace_id
+        )
+        .fetch(&pool);
+
+        let mut exported_rows = 0;
+
+        while let Some(link) = links.next().await {
+            let row = match link {
+                Ok(link) => codec
+                    .encode(link, &mut buffer)
+                    .map(|_| buffer.split().freeze())
+                    .map_err(BoxError::from),
+                Err(err) => Err(BoxError::from(err)),
+            };
+
+            if let Err(err) = &row {
+                tracing::error!("Exporting the links failed: {}", err);
+            }
+
+            let failed = row.is_err();
+
+            // Sending only fails once the client went away, which also ends the query
+            if sender.send(row).await.is_err() || failed {
+                return;
+            }
+
+            exported_rows += 1;
+        }
+
+        tracing::debug!("Exported {} links", exported_rows);
+    });
+
+    let content_disposition = format!(
+        "attachment; filename=\"links-{}.csv\"",
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+
+    (
+        [
+            (CONTENT_TYPE, "text/csv".to_owned()),
+            (CONTENT_DISPOSITION, content_disposition),
+        ],
+        Body::from_stream(receiver),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/statistics/top",
+    params(TopLinksQuery),
+    responses(
+        (status = 200, description = "Most clicked links, most clicks first", body = Vec<TopLink>),
+        (status = 422, description = "Limit out of range")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn get_top_links(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Query(query): Query<TopLinksQuery>,
+) -> Result<Json<Vec<TopLink>>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_TOP_LINKS_LIMIT);
+
+    if !(1..=MAX_TOP_LINKS_LIMIT).contains(&limit) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "invalid_limit",
+            format!("limit must be between 1 and {}", MAX_TOP_LINKS_LIMIT),
+        )));
+    }
+
+    let fetch_top_links_timeout = config.db_query_timeout;
+
+    let top_links = timed_query(
+        "select_top_links",
+        fetch_top_links_timeout,
+        sqlx::query_as!(
+            TopLink,
+            r#"
+            select links.id, links.target_url, count(*) as "clicks!"
+            from links
+            join link_statistics on link_statistics.link_id = links.id
+            where links.deleted_at is null and links.workspace_id is not distinct from $2
+            group by links.id
+            order by 3 desc, links.id
+            limit $1
+            "#,
+            limit,
+            query.workspace_id
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Listed the top {} links", top_links.len());
+
+    Ok(Json(top_links))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit events, newest first", body = Vec<AuditEvent>),
+        (status = 422, description = "Limit out of range")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn get_audit_log(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEvent>>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_EVENTS_LIMIT);
+
+    if !(1..=MAX_AUDIT_EVENTS_LIMIT).contains(&limit) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "invalid_limit",
+            format!("limit must be between 1 and {}", MAX_AUDIT_EVENTS_LIMIT),
+        )));
+    }
+
+    let select_audit_log_timeout = config.db_query_timeout;
+
+    let events = timed_query(
+        "select_audit_log",
+        select_audit_log_timeout,
+        sqlx::query_as!(
+            AuditEvent,
+            r#"
+            select id, link_id, action, actor_key_id, old_value, new_value, created_at from audit_log
+            where ($1::text is null or link_id = $1)
+                and ($2::timestamptz is null or created_at >= $2)
+                and ($3::timestamptz is null or created_at < $3 or (created_at = $3 and id > $5))
+            order by created_at desc, id
+            limit $4
+            "#,
+            query.link_id,
+            query.from,
+            query.to,
+            limit,
+            query.before_id
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Listed {} audit events", events.len());
+
+    Ok(Json(events))
+}
+
+/// Bodies above the import limit are reported as such, everything else is a malformed body
+fn multipart_error(err: MultipartError) -> AppError {
+    let body = ErrorBody::new("invalid_multipart", err.body_text());
+
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge(body)
+    } else {
+        AppError::BadRequest(body)
+    }
+}
+
+async fn read_import_file(mut multipart: Multipart) -> Result<Vec<u8>, AppError> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(multipart_error)?
+    {
+        if field.name() == Some("file") {
+            let file = field.bytes().await.map_err(multipart_error)?;
+
+            return Ok(file.to_vec());
+        }
+    }
+
+    Err(AppError::UnprocessableEntity(ErrorBody::new("file_missing", "missing the file field")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/links/import",
+    request_body(content = LinkImportFile, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Links imported, rows that could not be imported are listed with a reason", body = LinkImportSummary),
+        (status = 413, description = "File larger than 10 MB"),
+        (status = 422, description = "Missing file or header row")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn import_links(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Json<LinkImportSummary>, AppError> {
+    let file = read_import_file(multipart).await?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file.as_slice());
+
+    let header = reader
+        .headers()
+        .map_err(|err| AppError::UnprocessableEntity(ErrorBody::new("invalid_csv", err.to_string())))?;
+
+    if header != IMPORT_HEADER.as_slice() {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "invalid_csv_header",
+            format!("the header row must be {}", IMPORT_HEADER.join(",")),
+        )));
+    }
+
+    let mut rows = Vec::new();
+    let mut seen_ids = HashSet::new();
+    let mut errors = Vec::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(LinkImportError {
+                    row: err.position().map_or(0, |position| position.line() as u32),
+                    reason: err.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let row = record.position().map_or(0, |position| position.line() as u32);
+        let id = &record[0];
+
+        if !custom_id_regex().is_match(id) {
+            errors.push(LinkImportError {
+                row,
+                reason: "id malformed".into(),
+            });
+            continue;
+        }
+
+        if state.link_id_blacklist.contains(id) {
+            errors.push(LinkImportError {
+                row,
+                reason: "id is reserved".into(),
+            });
+            continue;
+        }
+
+        let target_url = match parse_target_url(&record[1], &state.blocklist) {
+            Ok(target_url) => target_url,
+            Err(err) => {
+                errors.push(LinkImportError {
+                    row,
+                    reason: err.message().into(),
+                });
+                continue;
+            }
+        };
+
+        if !seen_ids.insert(id.to_owned()) {
+            errors.push(LinkImportError {
+                row,
+                reason: "id occurs more than once in the file".into(),
+            });
+            continue;
+        }
+
+        rows.push((row, id.to_owned(), target_url));
+    }
+
+    let mut staged = csv::Writer::from_writer(Vec::new());
+
+    for (_, id, target_url) in &rows {
+        staged.write_record([id, target_url]).map_err(internal_error)?;
+    }
+
+    let staged = staged.into_inner().map_err(internal_error)?;
+    let import_timeout = state.config.db_query_timeout;
+
+    let mut transaction = state.db.begin().await.map_err(internal_error)?;
+
+    // The rows are copied into a staging table first, because a copy straight into links
+    // would abort the whole import on the first id that already exists
+    sqlx::query("create temporary table link_imports (id text not null, target_url text not null) on commit drop")
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    timed_query("copy_link_imports", import_timeout, async {
+        let mut copy = transaction
+            .copy_in_raw("copy link_imports (id, target_url) from stdin with (format csv)")
+            .await?;
+        copy.send(staged).await?;
+        copy.finish().await
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let imported_ids: Vec<String> = timed_query(
+        "insert_link_imports",
+        import_timeout,
+        sqlx::query_scalar(
+            r#"
+            insert into links (id, target_url)
+            select id, target_url from link_imports
+            on conflict (id) do nothing
+            returning id
+            "#,
+        )
+        .fetch_all(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    let imported_ids: HashSet<String> = imported_ids.into_iter().collect();
+    let imported = imported_ids.len() as u32;
+
+    for (row, id, _) in rows {
+        if !imported_ids.contains(&id) {
+            errors.push(LinkImportError {
+                row,
+                reason: "id already exists".into(),
+            });
+        }
+    }
+
+    errors.sort_by_key(|error| error.row);
+
+    tracing::info!("Imported {} links, skipped {}", imported, errors.len());
+
+    Ok(Json(LinkImportSummary {
+        imported,
+        skipped: errors.len() as u32,
+        errors,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/links/bulk-delete",
+    request_body = BulkDeleteRequest,
+    responses(
+        (status = 200, description = "Links deleted, their statistics are kept", body = BulkDeleteSummary),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Too many ids")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn bulk_delete_links(
+    State(state): State<AppState>,
+    Json(bulk_delete): Json<BulkDeleteRequest>,
+) -> Result<Json<BulkDeleteSummary>, AppError> {
+    if bulk_delete.ids.len() > MAX_BULK_DELETE_IDS {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "too_many_ids",
+            format!("at most {} links can be deleted at once", MAX_BULK_DELETE_IDS),
+        )));
+    }
+
+    let bulk_delete_timeout = state.config.db_query_timeout;
+
+    // Admin calls are made with the global key, which has no id to attribute the changes to
+    let mut transaction = audit::begin(&state.db, None).await?;
+
+    let deleted_ids = timed_query(
+        "bulk_delete_links",
+        bulk_delete_timeout,
+        sqlx::query_scalar!(
+            "update links set deleted_at = now() where id = any($1) and deleted_at is null returning id",
+            &bulk_delete.ids
+        )
+        .fetch_all(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    for link_id in &deleted_ids {
+        invalidate_cached_link(&state, link_id).await;
+    }
+
+    let deleted_ids: HashSet<String> = deleted_ids.into_iter().collect();
+    let deleted = deleted_ids.len() as u32;
+
+    let mut seen_ids = HashSet::new();
+    let not_found = bulk_delete
+        .ids
+        .into_iter()
+        .filter(|id| !deleted_ids.contains(id) && seen_ids.insert(id.clone()))
+        .collect();
+
+    counter!("bulk_deleted_links_total", u64::from(deleted));
+
+    tracing::info!("Deleted {} links in bulk", deleted);
+
+    Ok(Json(BulkDeleteSummary { deleted, not_found }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/links",
+    params(
+        PruneLinksQuery,
+        ("Confirm-Destructive" = String, Header, description = "Must be yes")
+    ),
+    responses(
+        (status = 200, description = "Links deleted for good together with their statistics", body = LinksPruned),
+        (status = 400, description = "Deletion not confirmed")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn prune_links(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<PruneLinksQuery>,
+) -> Result<Json<LinksPruned>, AppError> {
+    let confirmed = headers
+        .get(CONFIRM_DESTRUCTIVE_HEADER)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"yes"));
+
+    if !confirmed {
+        return Err(AppError::BadRequest(ErrorBody::new(
+            "confirmation_required",
+            "the Confirm-Destructive header must be set to yes",
+        )));
+    }
+
+    let prune_links_timeout = state.config.db_query_timeout;
+
+    let mut deleted = 0;
+
+    // Links are deleted in batches, each within the query timeout, so that pruning many links
+    // neither times out nor locks all of them at once. A failed batch leaves the ones before
+    // it deleted, retrying continues with the rest. Like purging expired links, this removes
+    // the statistics through the cascading foreign key. Aliases go with their links, they are
+    // deleted explicitly to invalidate their cached copies.
+    loop {
+        let batch_ids = timed_query(
+            "prune_links",
+            prune_links_timeout,
+            sqlx::query_scalar!(
+                r#"
+                with batch as (
+                    select id from links where created_at < $1 and deleted_at is null limit $2
+                )
+                delete from links
+                where id in (select id from batch) or canonical_id in (select id from batch)
+                returning id
+                "#,
+                query.older_than,
+                PRUNE_BATCH_SIZE
+            )
+            .fetch_all(&state.db),
+        )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+
+        if batch_ids.is_empty() {
+            break;
+        }
+
+        for link_id in &batch_ids {
+            invalidate_cached_link(&state, link_id).await;
+        }
+
+        deleted += batch_ids.len() as i64;
+    }
+
+    counter!("bulk_link_purge_total", deleted as u64);
+
+    // Admin calls are always made with the global key
+    tracing::warn!(
+        "Api key {} pruned {} links created before {}",
+        GLOBAL_API_KEY_NAME,
+        deleted,
+        query.older_than
+    );
+
+    Ok(Json(LinksPruned { deleted }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/links/move",
+    request_body = MoveLinksRequest,
+    responses(
+        (status = 200, description = "Links moved, their ids stay the same", body = MoveLinksSummary),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Too many ids or workspace not found")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn move_links(
+    State(state): State<AppState>,
+    Json(move_links): Json<MoveLinksRequest>,
+) -> Result<Json<MoveLinksSummary>, AppError> {
+    if move_links.link_ids.len() > MAX_BULK_DELETE_IDS {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "too_many_ids",
+            format!("at most {} links can be moved at once", MAX_BULK_DELETE_IDS),
+        )));
+    }
+
+    let move_links_timeout = state.config.db_query_timeout;
+
+    // The `links_audit` trigger records every move with the workspace before and after it
+    let mut transaction = audit::begin(&state.db, None).await?;
+
+    // Links that already are in the target workspace are found but not updated, so that
+    // they get no audit event for a change that did not happen
+    let found_links = timed_query(
+        "move_links",
+        move_links_timeout,
+        sqlx::query!(
+            r#"
+            with moved_links as (
+                update links set workspace_id = $1
+                where id = any($2) and workspace_id is distinct from $1
+                returning id
+            )
+            select id as "id!", id in (select id from moved_links) as "moved!"
+            from links where id = any($2)
+            "#,
+            move_links.target_workspace_id,
+            &move_links.link_ids
+        )
+        .fetch_all(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(|err| match err {
+        Error::Database(db_err) if db_err.kind() == ErrorKind::ForeignKeyViolation => {
+            AppError::UnprocessableEntity(ErrorBody::new("workspace_not_found", "workspace not found"))
+        }
+        err => internal_error(err),
+    })?;
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    let moved = found_links.iter().filter(|link| link.moved).count() as u32;
+    let found_ids: HashSet<String> = found_links.into_iter().map(|link| link.id).collect();
+
+    let mut seen_ids = HashSet::new();
+    let not_found = move_links
+        .link_ids
+        .into_iter()
+        .filter(|id| !found_ids.contains(id) && seen_ids.insert(id.clone()))
+        .collect();
+
+    counter!("moved_links_total", u64::from(moved));
+
+    tracing::info!("Moved {} links to workspace {:?}", moved, move_links.target_workspace_id);
+
+    Ok(Json(MoveLinksSummary { moved, not_found }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/workspaces",
+    request_body = NewWorkspace,
+    responses(
+        (status = 201, description = "Created workspace", body = Workspace),
+        (status = 409, description = "Name already taken"),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Name is empty")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn create_workspace(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Json(new_workspace): Json<NewWorkspace>,
+) -> Result<(StatusCode, Json<Workspace>), AppError> {
+    let name = new_workspace.name.trim();
+
+    if name.is_empty() {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new("invalid_name", "name must not be empty")));
+    }
+
+    let insert_workspace_timeout = config.db_query_timeout;
+
+    let workspace = timed_query(
+        "insert_workspace",
+        insert_workspace_timeout,
+        sqlx::query_as!(
+            Workspace,
+            "insert into workspaces(name) values ($1) returning id, name, created_at",
+            name
+        )
+        .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(|err| match err {
+        Error::Database(db_err) if db_err.kind() == ErrorKind::UniqueViolation => {
+            AppError::Conflict(ErrorBody::new("workspace_name_taken", "workspace name already taken"))
+        }
+        err => internal_error(err),
+    })?;
+
+    tracing::info!("Created workspace with id {} named {}", workspace.id, workspace.name);
+
+    Ok((StatusCode::CREATED, Json(workspace)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/workspaces",
+    responses((status = 200, description = "All workspaces", body = Vec<Workspace>)),
+    security(("api_key" = []))
+)]
+pub async fn list_workspaces(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+) -> Result<Json<Vec<Workspace>>, AppError> {
+    let fetch_workspaces_timeout = config.db_query_timeout;
+
+    let workspaces = timed_query(
+        "select_workspaces",
+        fetch_workspaces_timeout,
+        sqlx::query_as!(Workspace, "select id, name, created_at from workspaces order by created_at")
+            .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Listed {} workspaces", workspaces.len());
+
+    Ok(Json(workspaces))
+}
@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use axum::{middleware, Router};
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::{DefaultBodyLimit, Request};
+use axum::handler::Handler;
+use axum::http::{Extensions, HeaderMap, StatusCode, Version};
+use axum::routing::{delete, get, patch, post};
+use axum_prometheus::PrometheusMetricLayer;
+use metrics_exporter_prometheus::PrometheusHandle;
+use sentry::integrations::tower::{NewSentryLayer, SentryHttpLayer};
+use tower::ServiceBuilder;
+use tower::timeout::TimeoutLayer;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
+use tower_http::compression::{CompressionLayer, CompressionLevel};
+use tower_http::catch_panic::CatchPanicLayer;
+use tower_http::cors::CorsLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::admin::{
+    bulk_delete_links,
+    clear_link_statistics,
+    create_api_key,
+    create_workspace,
+    export_links,
+    get_audit_log,
+    get_raw_link_statistics,
+    get_top_links,
+    import_links,
+    list_api_keys,
+    list_deleted_links,
+    list_workspaces,
+    move_links,
+    prune_links,
+    revoke_api_key,
+    rotate_api_key,
+    update_settings,
+};
+use crate::auth::{admin_auth, auth, super_admin_auth};
+use crate::docs::ApiDoc;
+use crate::error::{handle_request_timeout, normalize_server_errors};
+use crate::extract::{require_json, require_json_or_form};
+#[cfg(feature = "graphql")]
+use crate::graphql;
+use crate::maintenance::{disable_maintenance_mode, enable_maintenance_mode, reject_writes_during_maintenance};
+use crate::preview::get_link_preview;
+use crate::rate_limit::{rate_limit, RateLimiter};
+use crate::routes::{
+    activate_link,
+    copy_link,
+    create_alias,
+    create_link,
+    create_links_in_bulk,
+    deactivate_link,
+    delete_link,
+    get_link_info,
+    get_link_statistics,
+    get_link_statistics_devices,
+    get_link_statistics_ips,
+    get_link_statistics_summary,
+    get_link_statistics_timeseries,
+    get_qr_code,
+    health,
+    link_exists,
+    list_links,
+    redirect,
+    restore_link,
+    update_link,
+    upsert_link,
+};
+use crate::security_headers::security_headers;
+use crate::shutdown::{track_in_flight_requests, InFlightRequests};
+use crate::state::AppState;
+use crate::telemetry::{remote_context, report_server_errors};
+
+/// What the router is built from besides the state. `main` reads all of it from the
+/// environment, tests build it themselves. The metrics recorder is global, so its
+/// layer and handle can only be created once per process and are passed in.
+pub struct AppOptions {
+    pub prometheus_layer: PrometheusMetricLayer<'static>,
+    pub metric_handle: PrometheusHandle,
+    pub create_rate_limiter: Arc<RateLimiter>,
+    pub redirect_rate_limiter: Arc<RateLimiter>,
+    pub cors_layer: CorsLayer,
+    pub compression_level: i32,
+    pub max_request_body_bytes: usize,
+    pub import_max_body_bytes: usize,
+    pub in_flight_requests: InFlightRequests,
+}
+
+/// Builds every route of the service with all of its middlewares. Clients have to be
+/// served with connect info, as redirects and their rate limits need the peer address.
+pub fn app(state: AppState, options: AppOptions) -> Router {
+    let AppOptions {
+        prometheus_layer,
+        metric_handle,
+        create_rate_limiter,
+        redirect_rate_limiter,
+        cors_layer,
+        compression_level,
+        max_request_body_bytes,
+        import_max_body_bytes,
+        in_flight_requests,
+    } = options;
+
+    // Redirects have empty bodies, so there is nothing to gain from compressing them
+    let compression_layer = CompressionLayer::new()
+        .quality(CompressionLevel::Precise(compression_level))
+        .compress_when(DefaultPredicate::new().and(
+            |status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions| !status.is_redirection(),
+        ));
+
+    let admin_routes = Router::new()
+        .route("/keys", post(create_api_key.layer(middleware::from_fn(require_json))).get(list_api_keys))
+        .route("/keys/:id", delete(revoke_api_key))
+        .route("/keys/:id/rotate", post(rotate_api_key))
+        .route("/settings", patch(update_settings.layer(middleware::from_fn(require_json))))
+        .route("/links", delete(prune_links))
+        .route("/links/bulk-delete", post(bulk_delete_links.layer(middleware::from_fn(require_json))))
+        .route("/links/deleted", get(list_deleted_links))
+        .route("/links/export", get(export_links))
+        .route("/links/import", post(import_links).layer(DefaultBodyLimit::max(import_max_body_bytes)))
+        .route("/links/:id/raw-statistics", get(get_raw_link_statistics))
+        .route("/links/:id/statistics/clear", post(clear_link_statistics))
+        .route("/statistics/top", get(get_top_links))
+        .route("/audit", get(get_audit_log))
+        .route("/maintenance/enable", post(enable_maintenance_mode))
+        .route("/maintenance/disable", post(disable_maintenance_mode))
+        .route_layer(middleware::from_fn_with_state(state.clone(), admin_auth));
+
+    // Workspaces span all keys, so not even the global key is allowed to manage them or move links between them
+    let super_admin_routes = Router::new()
+        .route("/workspaces", post(create_workspace.layer(middleware::from_fn(require_json))).get(list_workspaces))
+        .route("/links/move", post(move_links.layer(middleware::from_fn(require_json))))
+        .route_layer(middleware::from_fn_with_state(state.clone(), super_admin_auth));
+
+    // These are called server-to-server by monitoring and orchestration, so they do not
+    // need to be restricted to the origins configured for browser clients
+    let operational_routes = Router::new()
+        .route("/metrics", get(|| async move { metric_handle.render() }))
+        .route("/health", get(health))
+        .layer(CorsLayer::permissive());
+
+    #[cfg(feature = "graphql")]
+    let graphql_routes = graphql::routes(state.clone(), create_rate_limiter.clone());
+    #[cfg(not(feature = "graphql"))]
+    let graphql_routes = Router::new();
+
+    Router::new()
+        .route(
+            "/create",
+            post(
+                create_link
+                    .layer(middleware::from_fn(require_json_or_form))
+                    .layer(middleware::from_fn_with_state(create_rate_limiter, rate_limit))))
+        .route("/bulk", post(create_links_in_bulk.layer(middleware::from_fn(require_json))))
+        .route("/links", get(list_links))
+        .route("/:id/info", get(get_link_info))
+        .route("/:id/deactivate", post(deactivate_link))
+        .route("/:id/activate", post(activate_link))
+        .route("/:id/restore", post(restore_link))
+        .route("/:id/alias", post(create_alias.layer(middleware::from_fn(require_json))))
+        .route("/:id/copy", post(copy_link))
+        .route("/:id/preview", get(get_link_preview))
+        .route("/:id/statistics", get(get_link_statistics))
+        .route("/:id/statistics/timeseries", get(get_link_statistics_timeseries))
+        .route("/:id/statistics/ips", get(get_link_statistics_ips))
+        .route("/:id/statistics/devices", get(get_link_statistics_devices))
+        .route("/:id/statistics/summary", get(get_link_statistics_summary))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+        .route(
+            "/:id",
+            patch(update_link.layer(middleware::from_fn(require_json)))
+                .put(upsert_link.layer(middleware::from_fn(require_json)))
+                .delete(delete_link)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+                .get(redirect.layer(middleware::from_fn_with_state(redirect_rate_limiter.clone(), rate_limit)))
+                .head(link_exists.layer(middleware::from_fn_with_state(redirect_rate_limiter, rate_limit))))
+        .route("/:id/qr", get(get_qr_code))
+        .nest("/admin", admin_routes.merge(super_admin_routes))
+        .merge(graphql_routes)
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+        .layer(DefaultBodyLimit::max(max_request_body_bytes))
+        .layer(middleware::from_fn_with_state(state.clone(), reject_writes_during_maintenance))
+        .layer(cors_layer)
+        .merge(operational_routes)
+        // Wraps the auth middlewares as well, so that their queries can't hold a connection forever either
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_request_timeout))
+                .layer(TimeoutLayer::new(state.config.request_timeout)),
+        )
+        // A panicking handler only fails its own request instead of dropping the connection
+        .layer(CatchPanicLayer::new())
+        .layer(middleware::from_fn(security_headers))
+        .layer(compression_layer)
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(|req: &Request| {
+            // Set by the request id layer, so every log line of a request carries the same id
+            let request_id = req
+                .extensions()
+                .get::<RequestId>()
+                .and_then(|request_id| request_id.header_value().to_str().ok())
+                .unwrap_or_default();
+
+            let span = tracing::debug_span!(
+                "request",
+                method = %req.method(),
+                uri = %req.uri(),
+                version = ?req.version(),
+                request_id,
+            );
+            span.set_parent(remote_context(req.headers()));
+
+            span
+        }))
+        // Inside the request id layer, so that Sentry events carry the request id as well
+        .layer(middleware::from_fn(report_server_errors))
+        .layer(SentryHttpLayer::new())
+        .layer(NewSentryLayer::<Request>::new_from_top())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(prometheus_layer)
+        .layer(middleware::from_fn_with_state(in_flight_requests, track_in_flight_requests))
+        // Outermost, so that it also sees the request id and everything the other layers respond with
+        .layer(middleware::map_response(normalize_server_errors))
+        .with_state(state)
+}
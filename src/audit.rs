@@ -0,0 +1,21 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::utils::internal_error;
+
+/// Starts the transaction a change of links has to run in to be attributed to the
+/// key that made it. The `links_audit` trigger records every change within the same
+/// transaction, so a change is never committed without its audit event.
+pub async fn begin(pool: &PgPool, actor_key_id: Option<Uuid>) -> Result<Transaction<'static, Postgres>, AppError> {
+    let mut transaction = pool.begin().await.map_err(internal_error)?;
+
+    // The global key has no id, which the trigger records as no actor like changes of the service itself
+    sqlx::query("select set_config('link_shortener.actor_key_id', $1, true)")
+        .bind(actor_key_id.map(|id| id.to_string()).unwrap_or_default())
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(transaction)
+}
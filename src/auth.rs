@@ -1,11 +1,62 @@
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
 use axum::extract::{Request, State};
-use axum::http::StatusCode;
+use axum::http::HeaderValue;
 use axum::middleware::Next;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use metrics::increment_counter;
 use sha3::{Sha3_256, Digest};
 use sqlx::PgPool;
-use crate::utils::internal_error;
+use uuid::Uuid;
+
+use crate::config::{Config, KeyAlgorithm};
+use crate::error::{AppError, ErrorBody};
+use crate::state::AppState;
+use crate::utils::{internal_error, timed_query};
+
+const SHA3_PREFIX: &str = "$sha3$";
+
+const ARGON2ID_PREFIX: &str = "$argon2id$";
+
+pub const AUTHENTICATED_KEY_NAME_HEADER: &str = "x-authenticated-key-name";
+
+/// The global key has no name of its own
+pub const GLOBAL_API_KEY_NAME: &str = "global";
+
+const SUPER_ADMIN_API_KEY_NAME: &str = "super-admin";
+
+/// Argon2 keys issued before keys started with their id have to be verified one by one,
+/// which any caller can trigger with a made up key, so only the newest of them are tried
+const MAX_KEYS_WITHOUT_ID: i64 = 8;
+
+/// The key a request was authenticated with, which is available to all handlers
+/// behind `auth` and `admin_auth`. The global key has no id and belongs to no
+/// workspace, just like the links of the deployment before workspaces existed.
+#[derive(Clone, Copy)]
+pub struct AuthenticatedApiKey {
+    pub id: Option<Uuid>,
+    pub workspace_id: Option<Uuid>,
+}
+
+impl AuthenticatedApiKey {
+    const GLOBAL: Self = Self { id: None, workspace_id: None };
+}
+
+struct ActiveApiKey {
+    id: Uuid,
+    name: String,
+    workspace_id: Option<Uuid>,
+}
+
+struct Argon2ApiKey {
+    id: Uuid,
+    name: String,
+    workspace_id: Option<Uuid>,
+    key_hash: String,
+}
 
 struct Setting {
     #[allow(dead_code)]
@@ -13,49 +64,306 @@ struct Setting {
     encrypted_global_api_key: String,
 }
 
-pub async fn auth(
-    State(pool): State<PgPool>,
-    req: Request,
-    next: Next,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let labels = [("uri", format!("{}!", req.uri()))];
+fn sha3_hash(api_key: &str) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(api_key.as_bytes());
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hashes an api key for storage. The hash is prefixed with its algorithm, which
+/// lets verification pick the right algorithm regardless of the configured one.
+pub fn hash_api_key(api_key: &str, algorithm: KeyAlgorithm) -> String {
+    match algorithm {
+        KeyAlgorithm::Sha3_256 => format!("{}{}", SHA3_PREFIX, sha3_hash(api_key)),
+        KeyAlgorithm::Argon2id { time_cost, memory_kb } => {
+            let params = Params::new(memory_kb, time_cost, Params::DEFAULT_P_COST, None)
+                .expect("The argon2 parameters are validated when the config is loaded");
+            let salt = SaltString::generate(&mut OsRng);
 
-    let api_key = req
-        .headers()
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password(api_key.as_bytes(), &salt)
+                .expect("Hashing with validated parameters and a generated salt should always succeed")
+                .to_string()
+        }
+    }
+}
+
+/// Keys are issued as `<key id>.<secret>`, so that a salted argon2 hash, which cannot be
+/// looked up, only has to be verified against the one key the id names
+pub fn format_api_key(id: Uuid, secret: &str) -> String {
+    format!("{}.{}", id, secret)
+}
+
+fn api_key_id(api_key: &str) -> Option<Uuid> {
+    api_key
+        .split_once('.')
+        .and_then(|(id, _)| Uuid::parse_str(id).ok())
+}
+
+fn verify_api_key(api_key: &str, stored_hash: &str) -> bool {
+    if stored_hash.starts_with(ARGON2ID_PREFIX) {
+        // The parameters are part of the stored hash, so changing them only affects new keys
+        return PasswordHash::new(stored_hash).is_ok_and(|hash| {
+            Argon2::default().verify_password(api_key.as_bytes(), &hash).is_ok()
+        });
+    }
+
+    // Hashes stored before the algorithm could be chosen are sha3 without a prefix
+    stored_hash.strip_prefix(SHA3_PREFIX).unwrap_or(stored_hash) == sha3_hash(api_key)
+}
+
+/// Argon2 is deliberately slow, so it is verified outside of the async runtime.
+async fn matches_api_key(api_key: &str, stored_hash: String) -> Result<bool, AppError> {
+    let api_key = api_key.to_owned();
+
+    tokio::task::spawn_blocking(move || verify_api_key(&api_key, &stored_hash))
+        .await
+        .map_err(internal_error)
+}
+
+/// New keys are hashed outside of the async runtime as well, see `matches_api_key`.
+pub(crate) async fn hashed_api_key(api_key: &str, algorithm: KeyAlgorithm) -> Result<String, AppError> {
+    let api_key = api_key.to_owned();
+
+    tokio::task::spawn_blocking(move || hash_api_key(&api_key, algorithm))
+        .await
+        .map_err(internal_error)
+}
+
+fn provided_api_key(
+    req: &Request,
+    labels: &[(&'static str, String)],
+) -> Result<String, AppError> {
+    req.headers()
         .get("x-api-key")
-        .map(|value| value.to_str().unwrap_or_default())
+        .map(|value| value.to_str().unwrap_or_default().to_owned())
         .ok_or_else(|| {
             tracing::error!("Unauthroized call to API: No key header received");
-            increment_counter!("unauthenticated_calls_count", &labels);
+            increment_counter!("unauthenticated_calls_count", labels);
 
-            (StatusCode::UNAUTHORIZED, "Unauthorized".into())
-        })?;
+            AppError::Unauthorized(ErrorBody::new("unauthorized", "Unauthorized"))
+        })
+}
 
-    let fetch_setting_timeout = tokio::time::Duration::from_millis(300);
+/// Taken by every update of the settings, so that reads, which take it shared and
+/// therefore do not wait for each other, never see a rotation half done.
+pub const SETTINGS_WRITE_LOCK: &str = "select pg_advisory_xact_lock(hashtext('settings'))";
 
-    let setting = tokio::time::timeout(
-        fetch_setting_timeout,
+const SETTINGS_READ_LOCK: &str = "select pg_advisory_xact_lock_shared(hashtext('settings'))";
+
+async fn is_global_api_key(
+    pool: &PgPool,
+    query_timeout: Duration,
+    provided_api_key: &str,
+) -> Result<bool, AppError> {
+    let mut transaction = pool.begin().await.map_err(internal_error)?;
+
+    timed_query(
+        "lock_settings",
+        query_timeout,
+        sqlx::query(SETTINGS_READ_LOCK).execute(&mut *transaction)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let setting = timed_query(
+        "select_settings",
+        query_timeout,
         sqlx::query_as!(
             Setting,
             "select id, encrypted_global_api_key from settings where id = $1",
             "DEFAULT_SETTINGS"
         )
-            .fetch_one(&pool)
+            .fetch_one(&mut *transaction)
     )
     .await
     .map_err(internal_error)?
     .map_err(internal_error)?;
 
-    let mut hasher = Sha3_256::new();
-    hasher.update(api_key.as_bytes());
-    let provided_api_key = hasher.finalize();
+    // The lock is released before the key is verified, which can take a while for argon2 hashes
+    transaction.commit().await.map_err(internal_error)?;
 
-    if setting.encrypted_global_api_key != format!("{provided_api_key:x}") {
-        tracing::error!("Unauthorized call to API: Incorrect key supplied");
+    matches_api_key(provided_api_key, setting.encrypted_global_api_key).await
+}
+
+/// Keys that are being rotated stay active until their grace period ends.
+async fn active_api_key(
+    pool: &PgPool,
+    query_timeout: Duration,
+    provided_api_key: &str,
+) -> Result<Option<ActiveApiKey>, AppError> {
+    let sha3_hash = sha3_hash(provided_api_key);
+
+    let api_key = timed_query(
+        "select_api_key",
+        query_timeout,
+        sqlx::query_as!(
+            ActiveApiKey,
+            r#"
+            select id, name, workspace_id from api_keys
+            where key_hash = any($1) and revoked_at is null and (grace_period_ends_at is null or grace_period_ends_at > now())
+            "#,
+            &[format!("{}{}", SHA3_PREFIX, sha3_hash), sha3_hash]
+        )
+            .fetch_optional(pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    if api_key.is_some() {
+        return Ok(api_key);
+    }
+
+    // Argon2 hashes are salted, so they cannot be looked up. Keys with an id are verified
+    // against that one key, the others against each key issued without an id.
+    let argon2_keys = match api_key_id(provided_api_key) {
+        Some(api_key_id) => timed_query(
+            "select_argon2_api_key",
+            query_timeout,
+            sqlx::query_as!(
+                Argon2ApiKey,
+                r#"
+                select id, name, workspace_id, key_hash from api_keys
+                where id = $1 and starts_with(key_hash, $2) and revoked_at is null and (grace_period_ends_at is null or grace_period_ends_at > now())
+                "#,
+                api_key_id,
+                ARGON2ID_PREFIX
+            )
+                .fetch_all(pool)
+        )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?,
+        None => timed_query(
+            "select_argon2_api_keys_without_id",
+            query_timeout,
+            sqlx::query_as!(
+                Argon2ApiKey,
+                r#"
+                select id, name, workspace_id, key_hash from api_keys
+                where not key_has_id and starts_with(key_hash, $1) and revoked_at is null and (grace_period_ends_at is null or grace_period_ends_at > now())
+                order by created_at desc
+                limit $2
+                "#,
+                ARGON2ID_PREFIX,
+                MAX_KEYS_WITHOUT_ID
+            )
+                .fetch_all(pool)
+        )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?,
+    };
+
+    for argon2_key in argon2_keys {
+        if matches_api_key(provided_api_key, argon2_key.key_hash).await? {
+            return Ok(Some(ActiveApiKey {
+                id: argon2_key.id,
+                name: argon2_key.name,
+                workspace_id: argon2_key.workspace_id,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Tells the caller which key it was authenticated with, which helps when a proxy or
+/// secret manager injects the key. Only ever the name, never the key or its hash.
+fn with_key_name(mut response: Response, api_key_name: &str) -> Response {
+    // Names are free text, those that are no valid header value are left out
+    if let Ok(value) = HeaderValue::from_str(api_key_name) {
+        response.headers_mut().insert(AUTHENTICATED_KEY_NAME_HEADER, value);
+    }
+
+    response
+}
+
+pub async fn auth(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let labels = [("uri", format!("{}!", req.uri()))];
+
+    let provided_api_key = provided_api_key(&req, &labels)?;
+
+    let (api_key, api_key_name) = if is_global_api_key(&pool, config.db_query_timeout, &provided_api_key).await? {
+        (AuthenticatedApiKey::GLOBAL, GLOBAL_API_KEY_NAME.to_owned())
+    } else {
+        match active_api_key(&pool, config.db_query_timeout, &provided_api_key).await? {
+            Some(api_key) => (
+                AuthenticatedApiKey {
+                    id: Some(api_key.id),
+                    workspace_id: api_key.workspace_id,
+                },
+                api_key.name,
+            ),
+            None => {
+                tracing::error!("Unauthorized call to API: Incorrect key supplied");
+                increment_counter!("unauthenticated_calls_count", &labels);
+
+                return Err(AppError::Unauthorized(ErrorBody::new("unauthorized", "Unauthorized")));
+            }
+        }
+    };
+
+    req.extensions_mut().insert(api_key);
+
+    Ok(with_key_name(next.run(req).await, &api_key_name))
+}
+
+/// Only lets requests pass that are authenticated with the global bootstrap key
+/// stored in the settings, as they are allowed to manage all other keys.
+pub async fn admin_auth(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let labels = [("uri", format!("{}!", req.uri()))];
+
+    let provided_api_key = provided_api_key(&req, &labels)?;
+
+    if !is_global_api_key(&pool, config.db_query_timeout, &provided_api_key).await? {
+        tracing::error!("Unauthorized call to admin API: Incorrect key supplied");
+        increment_counter!("unauthenticated_calls_count", &labels);
+
+        return Err(AppError::Unauthorized(ErrorBody::new("unauthorized", "Unauthorized")));
+    }
+
+    req.extensions_mut().insert(AuthenticatedApiKey::GLOBAL);
+
+    Ok(with_key_name(next.run(req).await, GLOBAL_API_KEY_NAME))
+}
+
+/// Only lets requests pass that are authenticated with SUPER_ADMIN_API_KEY, which
+/// manages the workspaces. Without it being set, nobody is allowed to.
+pub async fn super_admin_auth(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let labels = [("uri", format!("{}!", req.uri()))];
+
+    let provided_api_key = provided_api_key(&req, &labels)?;
+
+    // Comparing the hashes keeps the comparison from telling how much of the key matched
+    let is_super_admin_api_key = state
+        .super_admin_api_key
+        .as_deref()
+        .is_some_and(|super_admin_api_key| sha3_hash(super_admin_api_key) == sha3_hash(&provided_api_key));
+
+    if !is_super_admin_api_key {
+        tracing::error!("Unauthorized call to super admin API: Incorrect key supplied");
         increment_counter!("unauthenticated_calls_count", &labels);
 
-        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+        return Err(AppError::Unauthorized(ErrorBody::new("unauthorized", "Unauthorized")));
     }
 
-    Ok(next.run(req).await)
-}
\ No newline at end of file
+    Ok(with_key_name(next.run(req).await, SUPER_ADMIN_API_KEY_NAME))
+}
@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use url::Url;
+
+/// A set of blocked domains that links must neither be created for nor redirect to.
+/// Blocking a domain also blocks all of its subdomains.
+#[derive(Clone, Default)]
+pub struct Blocklist {
+    domains: Arc<RwLock<HashSet<String>>>,
+}
+
+fn read_domains(path: &Path) -> std::io::Result<HashSet<String>> {
+    let content = std::fs::read_to_string(path)?;
+
+    Ok(content
+        .lines()
+        .map(|line| line.trim().trim_end_matches('.').to_lowercase())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect())
+}
+
+impl Blocklist {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let domains = read_domains(path)?;
+
+        tracing::debug!("Loaded {} blocked domains from {}", domains.len(), path.display());
+
+        Ok(Self {
+            domains: Arc::new(RwLock::new(domains)),
+        })
+    }
+
+    fn reload(&self, path: &Path) {
+        match read_domains(path) {
+            Ok(domains) => {
+                tracing::info!("Reloaded {} blocked domains from {}", domains.len(), path.display());
+
+                *self
+                    .domains
+                    .write()
+                    .expect("The blocklist lock should never be poisoned") = domains;
+            }
+            Err(err) => tracing::error!(
+                "Reloading the blocklist from {} failed with the following error: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    /// Reloads the blocklist every time the file at the given path changes. The
+    /// parent directory is watched instead of the file itself, because many
+    /// editors and deployment tools replace files instead of writing to them.
+    /// Watching stops as soon as the returned watcher is dropped.
+    pub fn watch(&self, path: &Path) -> notify::Result<RecommendedWatcher> {
+        let blocklist = self.clone();
+        let path: PathBuf = path.canonicalize()?;
+        let watched_path = path.clone();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                Ok(event) if event.paths.iter().any(|changed| *changed == watched_path) => {
+                    blocklist.reload(&watched_path)
+                }
+                Ok(_) => {}
+                Err(err) => tracing::error!("Watching the blocklist failed with the following error: {}", err),
+            }
+        })?;
+
+        let directory = path.parent().unwrap_or(&path);
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+        Ok(watcher)
+    }
+
+    pub fn is_blocked(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        let host = host.trim_end_matches('.').to_lowercase();
+        let domains = self
+            .domains
+            .read()
+            .expect("The blocklist lock should never be poisoned");
+
+        let mut domain = host.as_str();
+
+        loop {
+            if domains.contains(domain) {
+                return true;
+            }
+
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return false,
+            }
+        }
+    }
+}
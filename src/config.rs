@@ -0,0 +1,191 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::LevelFilter;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::ConnectOptions;
+
+const DEFAULT_DB_QUERY_TIMEOUT_MS: u64 = 300;
+
+const DEFAULT_DB_CONNECT_TIMEOUT_MS: u64 = 5000;
+
+const DEFAULT_DB_MAX_CONNECTIONS: u32 = 20;
+
+const DEFAULT_DB_MIN_CONNECTIONS: u32 = 2;
+
+const DEFAULT_DB_IDLE_TIMEOUT_SECS: u64 = 600;
+
+const DEFAULT_DB_MAX_LIFETIME_SECS: u64 = 1800;
+
+const DEFAULT_LOG_SLOW_QUERY_MS: u64 = 100;
+
+const DEFAULT_GLOBAL_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+const MAX_GLOBAL_REQUEST_TIMEOUT_SECS: u64 = 300;
+
+const MIN_TIMEOUT_MS: u64 = 50;
+
+const MAX_TIMEOUT_MS: u64 = 30_000;
+
+const DEFAULT_ARGON2_TIME_COST: u32 = argon2::Params::DEFAULT_T_COST;
+
+const DEFAULT_ARGON2_MEMORY_KB: u32 = argon2::Params::DEFAULT_M_COST;
+
+/// How newly stored api keys are hashed. Stored hashes carry their algorithm,
+/// so keys hashed with another algorithm before keep working.
+#[derive(Clone, Copy)]
+pub enum KeyAlgorithm {
+    Sha3_256,
+    Argon2id { time_cost: u32, memory_kb: u32 },
+}
+
+impl KeyAlgorithm {
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("AUTH_KEY_ALGORITHM").as_deref() {
+            Ok("sha3_256") | Err(_) => Ok(Self::Sha3_256),
+            Ok("argon2id") => {
+                let time_cost = env_or("ARGON2_TIME_COST", DEFAULT_ARGON2_TIME_COST)?;
+                let memory_kb = env_or("ARGON2_MEMORY_KB", DEFAULT_ARGON2_MEMORY_KB)?;
+
+                argon2::Params::new(memory_kb, time_cost, argon2::Params::DEFAULT_P_COST, None)
+                    .map_err(|err| format!("ARGON2_TIME_COST and ARGON2_MEMORY_KB are invalid: {}", err))?;
+
+                Ok(Self::Argon2id { time_cost, memory_kb })
+            }
+            Ok(value) => Err(format!(
+                "AUTH_KEY_ALGORITHM must be either sha3_256 or argon2id, got {}",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub db_query_timeout: Duration,
+    pub db_connect_timeout: Duration,
+    pub db_acquire_timeout: Duration,
+    pub db_max_connections: u32,
+    pub db_min_connections: u32,
+    pub db_idle_timeout: Duration,
+    pub db_max_lifetime: Duration,
+    pub db_test_before_acquire: bool,
+    pub slow_query_threshold: Duration,
+    pub request_timeout: Duration,
+    pub key_algorithm: KeyAlgorithm,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("{} must be a number, got {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn bool_from_env(name: &str, default: bool) -> Result<bool, String> {
+    match std::env::var(name).as_deref() {
+        Ok("true") => Ok(true),
+        Ok("false") => Ok(false),
+        Ok(value) => Err(format!("{} must be either true or false, got {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn timeout_from_env(name: &str, default_ms: u64) -> Result<Duration, String> {
+    let timeout_ms = env_or(name, default_ms)?;
+
+    if !(MIN_TIMEOUT_MS..=MAX_TIMEOUT_MS).contains(&timeout_ms) {
+        return Err(format!(
+            "{} must be between {} and {} ms, got {}",
+            name, MIN_TIMEOUT_MS, MAX_TIMEOUT_MS, timeout_ms
+        ));
+    }
+
+    Ok(Duration::from_millis(timeout_ms))
+}
+
+fn request_timeout_from_env() -> Result<Duration, String> {
+    let timeout_secs = env_or("GLOBAL_REQUEST_TIMEOUT_SECS", DEFAULT_GLOBAL_REQUEST_TIMEOUT_SECS)?;
+
+    if !(1..=MAX_GLOBAL_REQUEST_TIMEOUT_SECS).contains(&timeout_secs) {
+        return Err(format!(
+            "GLOBAL_REQUEST_TIMEOUT_SECS must be between 1 and {} s, got {}",
+            MAX_GLOBAL_REQUEST_TIMEOUT_SECS, timeout_secs
+        ));
+    }
+
+    Ok(Duration::from_secs(timeout_secs))
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, String> {
+        let db_max_connections = env_or("DB_MAX_CONNECTIONS", DEFAULT_DB_MAX_CONNECTIONS)?;
+        let db_min_connections = env_or("DB_MIN_CONNECTIONS", DEFAULT_DB_MIN_CONNECTIONS.min(db_max_connections))?;
+
+        if db_min_connections > db_max_connections {
+            return Err(format!(
+                "DB_MIN_CONNECTIONS must not exceed DB_MAX_CONNECTIONS of {}, got {}",
+                db_max_connections, db_min_connections
+            ));
+        }
+
+        let db_query_timeout = timeout_from_env("DB_QUERY_TIMEOUT_MS", DEFAULT_DB_QUERY_TIMEOUT_MS)?;
+        let db_query_timeout_ms = db_query_timeout.as_millis() as u64;
+        let db_acquire_timeout_ms = env_or("DB_ACQUIRE_TIMEOUT_MS", db_query_timeout_ms * 2 / 3)?;
+
+        // Queries time out with the wait for their connection included, so a longer wait
+        // would answer an exhausted pool with a timeout instead of asking to retry
+        if !(1..db_query_timeout_ms).contains(&db_acquire_timeout_ms) {
+            return Err(format!(
+                "DB_ACQUIRE_TIMEOUT_MS must be at least 1 and less than DB_QUERY_TIMEOUT_MS of {} ms, got {}",
+                db_query_timeout_ms, db_acquire_timeout_ms
+            ));
+        }
+
+        let db_acquire_timeout = Duration::from_millis(db_acquire_timeout_ms);
+
+        Ok(Self {
+            db_query_timeout,
+            db_connect_timeout: timeout_from_env("DB_CONNECT_TIMEOUT_MS", DEFAULT_DB_CONNECT_TIMEOUT_MS)?,
+            db_acquire_timeout,
+            db_max_connections,
+            db_min_connections,
+            db_idle_timeout: Duration::from_secs(env_or("DB_IDLE_TIMEOUT_SECS", DEFAULT_DB_IDLE_TIMEOUT_SECS)?),
+            db_max_lifetime: Duration::from_secs(env_or("DB_MAX_LIFETIME_SECS", DEFAULT_DB_MAX_LIFETIME_SECS)?),
+            db_test_before_acquire: bool_from_env("DB_TEST_BEFORE_ACQUIRE", true)?,
+            slow_query_threshold: Duration::from_millis(env_or("LOG_SLOW_QUERY_MS", DEFAULT_LOG_SLOW_QUERY_MS)?),
+            request_timeout: request_timeout_from_env()?,
+            key_algorithm: KeyAlgorithm::from_env()?,
+        })
+    }
+
+    /// The pool of the service. Keeping connections warm and replacing them regularly
+    /// means that a restarted database only costs the connections that were checked out.
+    pub fn db_pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            // DB_MAX_CONNECTIONS, `max_connections`
+            .max_connections(self.db_max_connections)
+            // DB_ACQUIRE_TIMEOUT_MS, `acquire_timeout`: how long a query waits for a free connection,
+            // opening a new one included, two thirds of DB_QUERY_TIMEOUT_MS by default
+            .acquire_timeout(self.db_acquire_timeout)
+            // DB_MIN_CONNECTIONS, `min_connections`: connections kept open even while idle
+            .min_connections(self.db_min_connections)
+            // DB_IDLE_TIMEOUT_SECS, `idle_timeout`: idle connections above the minimum are closed after it
+            .idle_timeout(self.db_idle_timeout)
+            // DB_MAX_LIFETIME_SECS, `max_lifetime`: connections are replaced after it, even if busy all along
+            .max_lifetime(self.db_max_lifetime)
+            // DB_TEST_BEFORE_ACQUIRE, `test_before_acquire`: pings connections before handing them out,
+            // which catches those broken by a restart of the database at the cost of a round trip
+            .test_before_acquire(self.db_test_before_acquire)
+    }
+
+    /// Logs every statement at debug level and statements slower than LOG_SLOW_QUERY_MS
+    /// as warnings, both with their text and duration.
+    pub fn db_connect_options(&self, db_url: &str) -> Result<PgConnectOptions, sqlx::Error> {
+        Ok(PgConnectOptions::from_str(db_url)?
+            .log_statements(LevelFilter::Debug)
+            .log_slow_statements(LevelFilter::Warn, self.slow_query_threshold))
+    }
+}
@@ -0,0 +1,114 @@
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::{admin, error, maintenance, preview, routes};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        routes::health,
+        routes::redirect,
+        routes::link_exists,
+        routes::get_link_info,
+        routes::get_qr_code,
+        routes::create_link,
+        routes::create_links_in_bulk,
+        routes::list_links,
+        routes::update_link,
+        routes::upsert_link,
+        routes::delete_link,
+        routes::deactivate_link,
+        routes::activate_link,
+        routes::restore_link,
+        routes::create_alias,
+        routes::copy_link,
+        routes::get_link_statistics,
+        routes::get_link_statistics_timeseries,
+        routes::get_link_statistics_ips,
+        routes::get_link_statistics_devices,
+        routes::get_link_statistics_summary,
+        preview::get_link_preview,
+        admin::create_api_key,
+        admin::list_api_keys,
+        admin::revoke_api_key,
+        admin::rotate_api_key,
+        admin::update_settings,
+        admin::list_deleted_links,
+        admin::export_links,
+        admin::get_raw_link_statistics,
+        admin::clear_link_statistics,
+        admin::get_top_links,
+        admin::get_audit_log,
+        admin::import_links,
+        admin::bulk_delete_links,
+        admin::prune_links,
+        admin::create_workspace,
+        admin::list_workspaces,
+        admin::move_links,
+        maintenance::enable_maintenance_mode,
+        maintenance::disable_maintenance_mode,
+    ),
+    components(schemas(
+        error::ErrorBody,
+        error::FieldError,
+        routes::Link,
+        routes::ShortLink,
+        routes::LinkInfo,
+        routes::LinkTarget,
+        routes::CreateLinkRequest,
+        routes::CreateAliasRequest,
+        routes::CopyLinkRequest,
+        routes::RedirectTarget,
+        routes::BulkLinkError,
+        routes::Health,
+        routes::HealthStatus,
+        routes::ComponentStatus,
+        routes::CountedLinkStatistic,
+        routes::PaginatedStatistics,
+        routes::PaginatedLinks,
+        routes::ClickBucket,
+        routes::IpStatistic,
+        routes::DeviceStatistic,
+        routes::LinkStatisticsSummary,
+        routes::TimeseriesBucket,
+        preview::LinkPreview,
+        admin::ApiKey,
+        admin::CreatedApiKey,
+        admin::NewApiKey,
+        admin::SettingsUpdate,
+        admin::SettingsUpdated,
+        admin::TopLink,
+        admin::RawLinkStatistic,
+        admin::AuditEvent,
+        admin::LinkImportFile,
+        admin::LinkImportError,
+        admin::LinkImportSummary,
+        admin::BulkDeleteRequest,
+        admin::BulkDeleteSummary,
+        admin::StatisticsCleared,
+        admin::Workspace,
+        admin::NewWorkspace,
+        admin::MoveLinksRequest,
+        admin::LinksPruned,
+        admin::MoveLinksSummary,
+        maintenance::MaintenanceStatus,
+    )),
+    modifiers(&ApiKeySecurity)
+)]
+pub struct ApiDoc;
+
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi
+            .components
+            .as_mut()
+            .expect("The components should always be registered");
+
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("x-api-key"))),
+        );
+    }
+}
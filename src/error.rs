@@ -0,0 +1,222 @@
+use axum::body::Body;
+use axum::http::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use metrics::increment_counter;
+
+const POOL_EXHAUSTED_RETRY_AFTER_SECS: &str = "5";
+
+/// The body of every error response. Clients should match on the code, the
+/// message is only meant for humans and may change.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    /// Machine readable snake_case code, e.g. `link_not_found`
+    #[schema(value_type = String)]
+    pub code: &'static str,
+    pub message: String,
+    /// Every constraint of the request a field failed, left out for other errors
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+impl ErrorBody {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with_fields(mut self, fields: Vec<FieldError>) -> Self {
+        self.fields = fields;
+        self
+    }
+}
+
+/// A constraint a single field of a request failed
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct FieldError {
+    /// Name of the field as it is sent, e.g. `targetUrl`
+    pub field: String,
+    /// Machine readable code of the constraint, e.g. `url` or `length`
+    pub code: String,
+    pub message: String,
+}
+
+impl IntoResponse for ErrorBody {
+    fn into_response(self) -> Response {
+        ([(CONTENT_TYPE, "application/json")], Json(self)).into_response()
+    }
+}
+
+/// Every error handlers and middlewares answer with. The variant decides the status,
+/// the body carries the code clients match on.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{}", .0.message)]
+    BadRequest(ErrorBody),
+    #[error("{}", .0.message)]
+    Unauthorized(ErrorBody),
+    #[error("{}", .0.message)]
+    Forbidden(ErrorBody),
+    #[error("{}", .0.message)]
+    NotFound(ErrorBody),
+    #[error("{}", .0.message)]
+    Conflict(ErrorBody),
+    #[error("{}", .0.message)]
+    Gone(ErrorBody),
+    #[error("{}", .0.message)]
+    PreconditionFailed(ErrorBody),
+    #[error("{}", .0.message)]
+    PayloadTooLarge(ErrorBody),
+    #[error("{}", .0.message)]
+    UnsupportedMediaType(ErrorBody),
+    #[error("{}", .0.message)]
+    UnprocessableEntity(ErrorBody),
+    #[error("{}", .0.message)]
+    UnavailableForLegalReasons(ErrorBody),
+    #[error("{}", .0.message)]
+    Internal(ErrorBody),
+    /// An upstream the request depends on, like the target page of a link, failed
+    #[error("{}", .0.message)]
+    BadGateway(ErrorBody),
+    #[error("{}", .0.message)]
+    ServiceUnavailable(ErrorBody),
+    /// Every connection of the pool was busy, which passes once the load drops
+    #[error("{}", .0.message)]
+    PoolExhausted(ErrorBody),
+    #[error("{}", .0.message)]
+    Timeout(ErrorBody),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::UnavailableForLegalReasons(_) => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            AppError::ServiceUnavailable(_) | AppError::PoolExhausted(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    pub fn into_body(self) -> ErrorBody {
+        match self {
+            AppError::BadRequest(body)
+            | AppError::Unauthorized(body)
+            | AppError::Forbidden(body)
+            | AppError::NotFound(body)
+            | AppError::Conflict(body)
+            | AppError::Gone(body)
+            | AppError::PreconditionFailed(body)
+            | AppError::PayloadTooLarge(body)
+            | AppError::UnsupportedMediaType(body)
+            | AppError::UnprocessableEntity(body)
+            | AppError::UnavailableForLegalReasons(body)
+            | AppError::Internal(body)
+            | AppError::BadGateway(body)
+            | AppError::ServiceUnavailable(body)
+            | AppError::PoolExhausted(body)
+            | AppError::Timeout(body) => body,
+        }
+    }
+}
+
+/// Counts every error by its code, which is a fixed set of values and therefore safe
+/// as a label. Failed upstreams, unavailability and timeouts tell clients when to retry, so their bodies
+/// are kept, while those of internal errors are replaced by `normalize_server_errors`.
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let keep_body = matches!(
+            self,
+            AppError::BadGateway(_) | AppError::ServiceUnavailable(_) | AppError::PoolExhausted(_) | AppError::Timeout(_)
+        );
+        let retry_after = matches!(self, AppError::PoolExhausted(_)).then_some(POOL_EXHAUSTED_RETRY_AFTER_SECS);
+        let body = self.into_body();
+
+        increment_counter!("error_responses_total", "code" => body.code);
+
+        let mut response = (status, body).into_response();
+
+        if let Some(retry_after) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+        }
+
+        if keep_body {
+            response.extensions_mut().insert(KeepServerErrorBody);
+        }
+
+        response
+    }
+}
+
+/// Marks server errors whose body is meant for clients, like the one of a degraded
+/// health check, so that `normalize_server_errors` keeps it.
+#[derive(Clone, Copy)]
+pub struct KeepServerErrorBody;
+
+/// Answers requests that ran into the global request timeout. Any other error of the
+/// middleware stack is infallible, so everything else is logged as an internal error.
+pub async fn handle_request_timeout(err: BoxError) -> Response {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        increment_counter!("request_timeouts_total");
+
+        return AppError::Timeout(ErrorBody::new("request_timeout", "The request took too long to be processed"))
+            .into_response();
+    }
+
+    tracing::error!("Processing the request failed: {}", err);
+
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerErrorBody<'a> {
+    code: &'static str,
+    message: &'static str,
+    request_id: &'a str,
+}
+
+/// Replaces the body of every server error with a generic one, so that neither
+/// database errors nor the plain text of panics or axum internals reach clients.
+/// The request id lets operators find the actual error in the logs.
+pub async fn normalize_server_errors(response: Response) -> Response {
+    if !response.status().is_server_error() || response.extensions().get::<KeepServerErrorBody>().is_some() {
+        return response;
+    }
+
+    let (mut parts, _) = response.into_parts();
+
+    let request_id = parts
+        .headers
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let body = serde_json::to_vec(&ServerErrorBody {
+        code: "internal_error",
+        message: "Internal server error",
+        request_id,
+    })
+    .expect("Serializing the server error body should never fail");
+
+    parts.headers.remove(CONTENT_ENCODING);
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,133 @@
+use axum::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{Form, Json};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::error::{AppError, ErrorBody};
+use crate::utils::body_validation_error;
+
+const FORM_MEDIA_TYPE: &str = "application/x-www-form-urlencoded";
+
+/// Accepts url encoded forms besides json, so that links can be created with
+/// a plain `curl -d`. Everything that is not a form is treated as json, which
+/// keeps the error messages for malformed json bodies as they were. Either way the
+/// body is validated like one extracted with `ValidatedJson`.
+pub struct JsonOrForm<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonOrForm<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_form = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with(FORM_MEDIA_TYPE));
+
+        let value = if is_form {
+            let Form(value) = Form::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+
+            value
+        } else {
+            let Json(value) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+
+            value
+        };
+
+        value
+            .validate()
+            .map_err(|errors| body_validation_error(errors).into_response())?;
+
+        Ok(Self(value))
+    }
+}
+
+/// A json body that may be left out. Unlike `Option<Json<T>>`, which treats every
+/// rejection as a missing body, only an empty body is `None`, a malformed one is
+/// rejected like by the json extractor.
+pub struct OptionalJson<T>(pub Option<T>);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for OptionalJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if bytes.is_empty() {
+            return Ok(Self(None));
+        }
+
+        let Json(value) = Json::<T>::from_request(Request::from_parts(parts, Body::from(bytes)), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(Self(Some(value)))
+    }
+}
+
+/// Matches what the json extractor accepts, including types like `application/merge-patch+json`
+fn is_json(media_type: &str) -> bool {
+    media_type == "application/json"
+        || media_type.strip_prefix("application/").is_some_and(|subtype| subtype.ends_with("+json"))
+}
+
+async fn require_content_type(req: Request, next: Next, allow_form: bool) -> Response {
+    let media_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|media_type| media_type.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    if is_json(&media_type) || (allow_form && media_type == FORM_MEDIA_TYPE) {
+        return next.run(req).await;
+    }
+
+    let expected = if allow_form {
+        "application/json or application/x-www-form-urlencoded"
+    } else {
+        "application/json"
+    };
+
+    AppError::UnsupportedMediaType(ErrorBody::new(
+        "unsupported_media_type",
+        format!("Content-Type must be {}", expected),
+    ))
+    .into_response()
+}
+
+/// Rejects bodies that are not json with a 415 before the json extractor gets to
+/// them, whose rejection is plain text and easily mistaken for a validation error.
+/// Only layered onto the handlers that extract a body, so endpoints like deactivating
+/// a link keep working without one.
+pub async fn require_json(req: Request, next: Next) -> Response {
+    require_content_type(req, next, false).await
+}
+
+/// Like `require_json`, but for handlers extracting `JsonOrForm`
+pub async fn require_json_or_form(req: Request, next: Next) -> Response {
+    require_content_type(req, next, true).await
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use metrics::gauge;
+use sqlx::PgPool;
+
+use crate::utils::timed_query;
+
+struct GaugeCounts {
+    active_links: i64,
+    active_api_keys: i64,
+    statistics_rows: i64,
+    top_link_clicks: i64,
+}
+
+/// Counts everything relevant for capacity planning in one round trip and
+/// publishes the results as gauges. Failures only leave the previous values in place.
+pub async fn refresh_gauges(pool: &PgPool, query_timeout: Duration) {
+    let counts = timed_query(
+        "select_gauge_counts",
+        query_timeout,
+        sqlx::query_as!(
+            GaugeCounts,
+            r#"
+            select
+                (select count(*) from links where deleted_at is null and (expires_at is null or expires_at > now())) as "active_links!",
+                (select count(*) from api_keys where revoked_at is null) as "active_api_keys!",
+                (select count(*) from link_statistics) as "statistics_rows!",
+                (
+                    select coalesce(max(clicks), 0) from (
+                        select count(*) as clicks from link_statistics
+                        join links on links.id = link_statistics.link_id
+                        where links.deleted_at is null
+                        group by link_statistics.link_id
+                    ) as link_clicks
+                ) as "top_link_clicks!"
+            "#
+        )
+        .fetch_one(pool)
+    )
+    .await;
+
+    match counts {
+        Ok(Ok(counts)) => {
+            gauge!("active_links_total", counts.active_links as f64);
+            gauge!("active_api_keys_total", counts.active_api_keys as f64);
+            gauge!("statistics_rows_total", counts.statistics_rows as f64);
+            gauge!("top_link_clicks", counts.top_link_clicks as f64);
+        }
+        Ok(Err(err)) => tracing::error!("Refreshing the gauges failed with the following error: {}", err),
+        Err(elapsed) => tracing::error!("Refreshing the gauges resulted in a timeout: {}", elapsed),
+    }
+}
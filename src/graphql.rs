@@ -0,0 +1,231 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::{Context, EmptySubscription, ErrorExtensions, Object, Schema};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware;
+use axum::response::{Html, IntoResponse};
+use axum::routing::post;
+use axum::{Extension, Json, Router};
+use metrics::increment_counter;
+use validator::Validate;
+
+use crate::auth::{auth, AuthenticatedApiKey};
+use crate::error::AppError;
+use crate::extract::JsonOrForm;
+use crate::maintenance::{maintenance_error, RETRY_AFTER_SECS};
+use crate::rate_limit::RateLimiter;
+use crate::routes::{
+    self,
+    CountedLinkStatistic,
+    CreateLinkRequest,
+    LinkInfo,
+    LinkTarget,
+    ListLinksQuery,
+    PaginatedLinks,
+    Pagination,
+    ShortLink,
+};
+use crate::state::AppState;
+use crate::utils::{body_validation_error, ValidatedJson};
+
+pub(crate) const GRAPHQL_PATH: &str = "/graphql";
+
+/// Deep enough for every query of the schema, like the tags of listed links
+const MAX_QUERY_DEPTH: usize = 8;
+
+const MAX_QUERY_COMPLEXITY: usize = 256;
+
+// The resolvers call the REST handlers, so that both apis share validation, caching
+// and metrics. Inputs are validated by the resolvers, as they skip the extractors that
+// validate bodies. Errors keep the machine readable code as an extension.
+
+fn graphql_error(err: AppError) -> async_graphql::Error {
+    let status = err.status();
+    let body = err.into_body();
+
+    async_graphql::Error::new(body.message).extend_with(|_, extensions| {
+        extensions.set("code", body.code);
+        extensions.set("status", status.as_u16());
+    })
+}
+
+/// Mutations share the path of queries, which stay answered during maintenance, so
+/// they are rejected here instead of by `reject_writes_during_maintenance`
+fn reject_during_maintenance(state: &AppState) -> async_graphql::Result<()> {
+    if !state.maintenance_mode.is_enabled() {
+        return Ok(());
+    }
+
+    Err(graphql_error(maintenance_error()).extend_with(|_, extensions| {
+        extensions.set("retryAfterSecs", RETRY_AFTER_SECS);
+    }))
+}
+
+type ApiSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn link(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<LinkInfo> {
+        let state = ctx.data::<AppState>()?;
+        let api_key = *ctx.data::<AuthenticatedApiKey>()?;
+
+        routes::get_link_info(State(state.db.clone()), State(state.config), Extension(api_key), Path(id))
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn links(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<u32>,
+        page_size: Option<u32>,
+        tag: Option<String>,
+    ) -> async_graphql::Result<PaginatedLinks> {
+        let state = ctx.data::<AppState>()?;
+        let api_key = *ctx.data::<AuthenticatedApiKey>()?;
+        let query = ListLinksQuery { page, page_size, q: None, tag };
+
+        routes::list_links(State(state.db.clone()), State(state.config), Extension(api_key), Query(query))
+            .await
+            .map(|Json(links)| links)
+            .map_err(graphql_error)
+    }
+
+    /// Click statistics grouped by referer and user agent, most clicks first
+    async fn link_statistics(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        page: Option<u32>,
+        page_size: Option<u32>,
+    ) -> async_graphql::Result<Vec<CountedLinkStatistic>> {
+        let state = ctx.data::<AppState>()?;
+        let api_key = *ctx.data::<AuthenticatedApiKey>()?;
+        let pagination = Pagination { page, page_size };
+
+        routes::get_link_statistics(
+            State(state.db.clone()),
+            State(state.config),
+            Extension(api_key),
+            Path(id),
+            Query(pagination),
+        )
+            .await
+            .map(|Json(statistics)| statistics.items)
+            .map_err(graphql_error)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Counts against the same rate limit as `POST /create`
+    async fn create_link(&self, ctx: &Context<'_>, input: CreateLinkRequest) -> async_graphql::Result<ShortLink> {
+        let state = ctx.data::<AppState>()?;
+
+        reject_during_maintenance(state)?;
+
+        let ConnectInfo(peer) = *ctx.data::<ConnectInfo<SocketAddr>>()?;
+
+        if let Err(retry_after) = ctx.data::<Arc<RateLimiter>>()?.acquire(peer.ip()) {
+            let labels = [("uri", format!("{}!", GRAPHQL_PATH))];
+
+            tracing::warn!("Rate limit exceeded for {}", peer.ip());
+            increment_counter!("rate_limited_calls_count", &labels);
+
+            let retry_after_secs = retry_after.as_secs_f64().ceil() as u64;
+
+            return Err(async_graphql::Error::new("Too many requests").extend_with(|_, extensions| {
+                extensions.set("code", "rate_limited");
+                extensions.set("status", StatusCode::TOO_MANY_REQUESTS.as_u16());
+                extensions.set("retryAfterSecs", retry_after_secs);
+            }));
+        }
+
+        let api_key = *ctx.data::<AuthenticatedApiKey>()?;
+        let headers = ctx.data::<HeaderMap>()?.clone();
+
+        input.validate().map_err(|errors| graphql_error(body_validation_error(errors)))?;
+
+        routes::create_link(State(state.clone()), Extension(api_key), headers, JsonOrForm(input))
+            .await
+            .map(|(_, link)| link)
+            .map_err(graphql_error)
+    }
+
+    async fn update_link(&self, ctx: &Context<'_>, id: String, input: LinkTarget) -> async_graphql::Result<ShortLink> {
+        let state = ctx.data::<AppState>()?;
+
+        reject_during_maintenance(state)?;
+
+        let api_key = *ctx.data::<AuthenticatedApiKey>()?;
+
+        input.validate().map_err(|errors| graphql_error(body_validation_error(errors)))?;
+
+        // Conditional updates need the If-Unmodified-Since header of the REST api
+        routes::update_link(State(state.clone()), Extension(api_key), Path(id), HeaderMap::new(), ValidatedJson(input))
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// Returns true once the link is deleted, its statistics are kept
+    async fn delete_link(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<bool> {
+        let state = ctx.data::<AppState>()?;
+
+        reject_during_maintenance(state)?;
+
+        let api_key = *ctx.data::<AuthenticatedApiKey>()?;
+
+        routes::delete_link(State(state.clone()), Extension(api_key), Path(id))
+            .await
+            .map(|_| true)
+            .map_err(graphql_error)
+    }
+}
+
+/// Passes what `auth` and the request headers tell about the client on to the resolvers,
+/// which need it to record the creator of new links and to scope links to its workspace.
+async fn execute(
+    State(schema): State<ApiSchema>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    peer: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    schema
+        .execute(request.into_inner().data(api_key).data(peer).data(headers))
+        .await
+        .into()
+}
+
+async fn playground() -> impl IntoResponse {
+    Html(playground_source(GraphQLPlaygroundConfig::new(GRAPHQL_PATH)))
+}
+
+/// Queries and mutations require an api key like the REST api, the playground
+/// itself is public and sends the key as a header configured in its settings. Depth
+/// and complexity are limited, so that a single query cannot do the work of thousands.
+pub fn routes(state: AppState, create_rate_limiter: Arc<RateLimiter>) -> Router<AppState> {
+    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(state.clone())
+        .data(create_rate_limiter)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish();
+
+    Router::new()
+        .route(
+            GRAPHQL_PATH,
+            post(execute)
+                .route_layer(middleware::from_fn_with_state(state, auth))
+                .get(playground),
+        )
+        .with_state(schema)
+}
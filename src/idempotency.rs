@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::error::{AppError, ErrorBody};
+use crate::routes::{Link, ShortLink, UtmParams};
+use crate::utils::{internal_error, timed_query};
+
+// Keys are remembered for 24 hours, which is hardcoded in the queries below and in
+// the purge job, as long as clients retry within a day they get the same link. Keys
+// are scoped by the method, creates and updates never replay each other, and by the
+// workspace of the api key, so that workspaces never replay each other's links.
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+struct IdempotentUpdate {
+    link_id: String,
+    response_body: Option<sqlx::types::Json<ShortLink>>,
+}
+
+pub fn idempotency_key(headers: &HeaderMap) -> Result<Option<Uuid>, AppError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| Uuid::parse_str(value.trim()).ok())
+        .map(Some)
+        .ok_or_else(|| {
+            AppError::UnprocessableEntity(ErrorBody::new(
+                "invalid_idempotency_key",
+                "idempotency key must be a uuid",
+            ))
+        })
+}
+
+/// The link that was created for the key within the last 24 hours, even if it got
+/// deleted since, because that is what the original request returned.
+pub async fn find_link(
+    pool: &PgPool,
+    query_timeout: Duration,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+) -> Result<Option<Link>, AppError> {
+    timed_query(
+        "select_idempotent_link",
+        query_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            select links.id, links.target_url, links.expires_at, links.is_permanent, links.max_clicks, links.webhook_url,
+                links.title, links.description, links.cache_control, links.robots_tag, links.utm_params as "utm_params: UtmParams", links.signed, links.active, links.created_at, links.updated_at, links.deleted_at,
+                link_tag_names(links.id) as "tags!"
+            from idempotency_keys
+            join links on links.id = idempotency_keys.link_id
+            where idempotency_keys.key = $1 and idempotency_keys.method = 'POST'
+                and idempotency_keys.workspace_id is not distinct from $2
+                and idempotency_keys.created_at > now() - interval '24 hours'
+            "#,
+            key,
+            workspace_id
+        )
+        .fetch_optional(pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)
+}
+
+/// Claims the key within the transaction that creates the link, before the link is
+/// inserted, so that concurrent retries wait for each other instead of both creating a
+/// link. Expired keys are taken over, as they might not have been purged yet. Returns
+/// false when the key is still valid, the link created for it should be replayed then.
+pub async fn claim_link(
+    connection: &mut PgConnection,
+    query_timeout: Duration,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+) -> Result<bool, AppError> {
+    let claimed_key = timed_query(
+        "claim_idempotency_key",
+        query_timeout,
+        sqlx::query_scalar!(
+            r#"
+            insert into idempotency_keys(key, method, workspace_id) values ($1, 'POST', $2)
+            on conflict (key, method, workspace_id) do update set link_id = null, created_at = now()
+            where idempotency_keys.created_at <= now() - interval '24 hours'
+            returning key
+            "#,
+            key,
+            workspace_id
+        )
+        .fetch_optional(connection)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    Ok(claimed_key.is_some())
+}
+
+/// Sets the link of a key claimed before within the same transaction
+pub async fn remember_link(
+    connection: &mut PgConnection,
+    query_timeout: Duration,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+    link_id: &str,
+) -> Result<(), AppError> {
+    timed_query(
+        "update_idempotency_key",
+        query_timeout,
+        sqlx::query!(
+            r#"
+            update idempotency_keys set link_id = $3
+            where key = $1 and method = 'POST' and workspace_id is not distinct from $2
+            "#,
+            key,
+            workspace_id,
+            link_id
+        )
+        .execute(connection)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    Ok(())
+}
+
+/// The response of the update made with the key within the last 24 hours. Updates are
+/// replayed as they were answered, not with the current state of the link, so that a
+/// retry cannot tell whether another update happened in between. Keys are bound to
+/// the link they updated first.
+pub async fn find_update(
+    pool: &PgPool,
+    query_timeout: Duration,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+    link_id: &str,
+) -> Result<Option<ShortLink>, AppError> {
+    let update = timed_query(
+        "select_idempotent_update",
+        query_timeout,
+        sqlx::query_as!(
+            IdempotentUpdate,
+            r#"
+            select link_id as "link_id!", response_body as "response_body: sqlx::types::Json<ShortLink>" from idempotency_keys
+            where key = $1 and method = 'PATCH' and workspace_id is not distinct from $2
+                and created_at > now() - interval '24 hours'
+            "#,
+            key,
+            workspace_id
+        )
+        .fetch_optional(pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    match update {
+        Some(update) if update.link_id != link_id => Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "idempotency_key_reused",
+            "idempotency key was already used to update another link",
+        ))),
+        Some(update) => Ok(update.response_body.map(|sqlx::types::Json(short_link)| short_link)),
+        None => Ok(None),
+    }
+}
+
+/// Claims the key for the link within the transaction of the update, before the link
+/// is updated, so that concurrent retries wait for each other instead of both updating
+/// it. Returns false when the key is still valid, its response should be replayed then.
+pub async fn claim_update(
+    connection: &mut PgConnection,
+    query_timeout: Duration,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+    link_id: &str,
+) -> Result<bool, AppError> {
+    let claimed_key = timed_query(
+        "claim_idempotent_update",
+        query_timeout,
+        sqlx::query_scalar!(
+            r#"
+            insert into idempotency_keys(key, method, workspace_id, link_id) values ($1, 'PATCH', $2, $3)
+            on conflict (key, method, workspace_id) do update
+            set link_id = excluded.link_id, response_body = null, created_at = now()
+            where idempotency_keys.created_at <= now() - interval '24 hours'
+            returning key
+            "#,
+            key,
+            workspace_id,
+            link_id
+        )
+        .fetch_optional(connection)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    Ok(claimed_key.is_some())
+}
+
+/// Stored within the transaction of the update, so that an update is never committed
+/// without the response a retry gets.
+pub async fn remember_update(
+    connection: &mut PgConnection,
+    query_timeout: Duration,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+    short_link: &ShortLink,
+) -> Result<(), AppError> {
+    timed_query(
+        "update_idempotent_update",
+        query_timeout,
+        sqlx::query!(
+            r#"
+            update idempotency_keys set response_body = $3
+            where key = $1 and method = 'PATCH' and workspace_id is not distinct from $2
+            "#,
+            key,
+            workspace_id,
+            sqlx::types::Json(short_link) as sqlx::types::Json<&ShortLink>
+        )
+        .execute(connection)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+pub mod app;
+pub mod auth;
+pub mod blocklist;
+pub mod config;
+pub mod gauges;
+pub mod idempotency;
+pub mod maintenance;
+pub mod purge;
+pub mod rate_limit;
+pub mod routes;
+pub mod seed;
+pub mod shutdown;
+pub mod state;
+pub mod telemetry;
+pub mod tls;
+
+mod admin;
+mod audit;
+mod docs;
+mod error;
+mod extract;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod preview;
+mod redis_cache;
+mod security_headers;
+mod signing;
+mod utils;
+mod webhook;
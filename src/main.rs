@@ -1,72 +1,588 @@
 use std::error::Error;
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use axum::{middleware, Router};
-use axum::routing::{get, patch, post};
-use axum_prometheus::PrometheusMetricLayer;
+use axum::Router;
+use axum::extract::Request;
+use axum::http::header::{CONTENT_TYPE, RETRY_AFTER};
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum_prometheus::utils::SECONDS_DURATION_BUCKETS;
+use axum_prometheus::{PrometheusMetricLayerBuilder, AXUM_HTTP_REQUESTS_DURATION_SECONDS};
+use axum_server::tls_rustls::RustlsConfig;
 use dotenvy::dotenv;
-use sqlx::postgres::PgPoolOptions;
-use tower_http::trace::TraceLayer;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use moka::future::Cache;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use crate::auth::auth;
-use crate::routes::{create_link, get_link_statistics, health, redirect, update_link};
-
-mod routes;
-mod utils;
-mod auth;
-
+use link_shortener::app::{app, AppOptions};
+use link_shortener::auth::{hash_api_key, AUTHENTICATED_KEY_NAME_HEADER};
+use link_shortener::blocklist::Blocklist;
+use link_shortener::config::{Config, KeyAlgorithm};
+use link_shortener::gauges::refresh_gauges;
+use link_shortener::idempotency::IDEMPOTENCY_KEY_HEADER;
+use link_shortener::maintenance::MaintenanceMode;
+use link_shortener::purge::{complete_key_rotations, purge_expired_idempotency_keys, purge_expired_links};
+use link_shortener::rate_limit::{
+    RateLimiter,
+    RATE_LIMIT_LIMIT_HEADER,
+    RATE_LIMIT_REMAINING_HEADER,
+    RATE_LIMIT_RESET_HEADER,
+};
+use link_shortener::routes::{
+    DEFAULT_ID_LENGTH,
+    DEFAULT_LINK_ID_BLACKLIST,
+    DEFAULT_REDIRECT_ROBOTS_TAG,
+    MAX_ID_LENGTH,
+    MIN_ID_LENGTH,
+    is_valid_robots_tag,
+};
+use link_shortener::seed::seed_global_api_key;
+use link_shortener::shutdown::{shutdown_signal, InFlightRequests};
+use link_shortener::state::{AppState, LinkCacheTtl};
+use link_shortener::telemetry::{init_sentry, otlp_tracer, sentry_event_filter};
+use link_shortener::tls;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv().ok();
 
-    tracing_subscriber::registry()
+    let mut args = std::env::args().skip(1);
+
+    match args.next().as_deref() {
+        // Prints the hash to store for a key, e.g. to seed the global api key of a new database
+        Some("hash-key") => {
+            let key = args.next().ok_or("Usage: link-shortener hash-key <key>")?;
+            println!("{}", hash_api_key(&key, KeyAlgorithm::from_env()?));
+
+            return Ok(());
+        }
+        // Stores INITIAL_API_KEY as the global api key and prints the key to send
+        Some("seed") => {
+            println!("{}", seed_global_api_key().await?);
+
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let environment = std::env::var("ENVIRONMENT").unwrap_or_else(|_| "development".into());
+
+    // Errors are only reported when a dsn is configured. The guard flushes pending events once dropped.
+    let sentry_guard = match std::env::var("SENTRY_DSN") {
+        Ok(dsn) => Some(init_sentry(&dsn, &environment)?),
+        Err(_) => None,
+    };
+
+    let sentry_layer = sentry_guard
+        .as_ref()
+        .map(|_| sentry::integrations::tracing::layer().event_filter(sentry_event_filter));
+
+    // Spans are only exported when a collector is configured, logs are written either way
+    let otel_layer = match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => Some(tracing_opentelemetry::layer().with_tracer(otlp_tracer(&endpoint)?)),
+        Err(_) => None,
+    };
+
+    let registry = tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "link_shortener=debug".into())
+                // Slow queries are logged as warnings by sqlx, all others only at debug level
+                .unwrap_or_else(|_| "link_shortener=debug,sqlx=warn".into())
         )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+        .with(otel_layer)
+        .with(sentry_layer);
+
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => registry.with(tracing_subscriber::fmt::layer().json()).init(),
+        _ => registry.with(tracing_subscriber::fmt::layer()).init(),
+    }
 
+    tracing::info!(
+        service_name = env!("CARGO_PKG_NAME"),
+        version = env!("CARGO_PKG_VERSION"),
+        environment,
+        "Starting service"
+    );
+
+    // All queries are checked against the schema at compile time. Builds without a database,
+    // like the CI, set SQLX_OFFLINE=true and use the query data in the .sqlx directory instead.
+    // Whenever a query or migration changes, apply the migrations to a local database and run
+    // `cargo sqlx prepare` afterwards, then commit the updated .sqlx directory. CI runs
+    // `cargo sqlx prepare --check` to catch query data that went out of sync.
     let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is a required environment variable");
 
-    let db = PgPoolOptions::new()
-        .max_connections(20)
-        .connect(&db_url)
+    let config = Config::from_env()?;
+
+    let db = config
+        .db_pool_options()
+        .connect_with(config.db_connect_options(&db_url)?)
+        .await
+        .map_err(|err| {
+            format!(
+                "Could not connect to the database, make sure that the user in DATABASE_URL exists and its credentials are correct: {}",
+                err
+            )
+        })?;
+
+    let can_create_tables: bool = sqlx::query_scalar("select has_schema_privilege(current_schema(), 'CREATE')")
+        .fetch_one(&db)
         .await?;
 
-    let (prometheus_layer, metric_handle) = PrometheusMetricLayer::pair();
-
-    let app = Router::new()
-        .route("/create", post(create_link))
-        .route("/:id/statistics", get(get_link_statistics))
-        .route_layer(middleware::from_fn_with_state(db.clone(), auth))
-        .route(
-            "/:id",
-            patch(update_link)
-                .route_layer(middleware::from_fn_with_state(db.clone(), auth))
-                .get(redirect))
-        .route("/metrics", get(|| async move { metric_handle.render() }))
-        .route("/health", get(health))
-        .layer(TraceLayer::new_for_http())
-        .layer(prometheus_layer)
-        .with_state(db);
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
+    if !can_create_tables {
+        return Err("The user in DATABASE_URL needs the CREATE privilege on the database schema to run migrations".into());
+    }
+
+    sqlx::migrate!().run(&db).await?;
+
+    tracing::info!("Database schema is up to date");
+
+    let base_url = std::env::var("BASE_URL")
+        .unwrap_or_else(|_| "http://localhost:3000".into())
+        .trim_end_matches('/')
+        .to_string();
+
+    let redirect_cache_capacity = std::env::var("REDIRECT_CACHE_CAPACITY")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(10_000);
+
+    // A ttl changed via the settings endpoint outlives restarts and wins over the environment
+    let stored_redirect_cache_ttl_secs = sqlx::query_scalar!(
+        "select redirect_cache_ttl_secs from settings where id = $1",
+        "DEFAULT_SETTINGS"
+    )
+    .fetch_optional(&db)
+    .await?
+    .flatten();
+
+    let redirect_cache_ttl_secs = match stored_redirect_cache_ttl_secs {
+        Some(ttl_secs) => u64::try_from(ttl_secs).unwrap_or_default(),
+        None => std::env::var("REDIRECT_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(60),
+    };
+
+    let link_cache_ttl = LinkCacheTtl::new(Duration::from_secs(redirect_cache_ttl_secs));
+
+    let link_cache = Cache::builder()
+        .max_capacity(redirect_cache_capacity)
+        .expire_after(link_cache_ttl.clone())
+        .build();
+
+    let redis = std::env::var("REDIS_URL")
+        .ok()
+        .map(|redis_url| {
+            deadpool_redis::Config::from_url(redis_url).create_pool(Some(deadpool_redis::Runtime::Tokio1))
+        })
+        .transpose()?;
+
+    let blocklist_path = std::env::var("BLOCKLIST_PATH").ok().map(PathBuf::from);
+
+    let blocklist = match &blocklist_path {
+        Some(path) => Blocklist::load(path).expect("BLOCKLIST_PATH must point to a readable file"),
+        None => Blocklist::default(),
+    };
+
+    // The watcher stops watching as soon as it gets dropped, so it has to live as long as the server
+    let _blocklist_watcher = blocklist_path
+        .as_ref()
+        .map(|path| blocklist.watch(path).expect("Could not watch the blocklist for changes"));
+
+    let id_length = match std::env::var("ID_LENGTH") {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("ID_LENGTH must be a number, got {}", value))?,
+        Err(_) => DEFAULT_ID_LENGTH,
+    };
+
+    if !(MIN_ID_LENGTH..=MAX_ID_LENGTH).contains(&id_length) {
+        return Err(format!("ID_LENGTH must be between {} and {}, got {}", MIN_ID_LENGTH, MAX_ID_LENGTH, id_length).into());
+    }
+
+    // Ids that clash with routes would make links unreachable or shadow the routes
+    let link_id_blacklist = std::env::var("LINK_ID_BLACKLIST")
+        .unwrap_or_else(|_| DEFAULT_LINK_ID_BLACKLIST.to_owned())
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    let signing_secret = std::env::var("SIGNING_SECRET").ok().filter(|secret| !secret.is_empty());
+
+    let signed_url_ttl = std::env::var("SIGNED_URL_TTL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(3600));
+
+    // Short links are kept out of search engines unless the deployment wants them listed,
+    // setting it empty sends no robots tag at all
+    let redirect_robots_tag = match std::env::var("REDIRECT_ROBOTS_TAG") {
+        Ok(robots_tag) if robots_tag.trim().is_empty() => None,
+        Ok(robots_tag) if !is_valid_robots_tag(robots_tag.trim()) => {
+            return Err("REDIRECT_ROBOTS_TAG must be printable ascii of at most 255 characters".into());
+        }
+        Ok(robots_tag) => Some(robots_tag.trim().to_owned()),
+        Err(_) => Some(DEFAULT_REDIRECT_ROBOTS_TAG.to_owned()),
+    };
+
+    let preview_cache_ttl = std::env::var("PREVIEW_CACHE_TTL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(86400));
+
+    let super_admin_api_key = std::env::var("SUPER_ADMIN_API_KEY").ok().filter(|api_key| !api_key.is_empty());
+
+    let maintenance_mode = MaintenanceMode::new(std::env::var("MAINTENANCE_MODE").is_ok_and(|value| value == "true"));
+
+    if maintenance_mode.is_enabled() {
+        tracing::warn!("Starting in maintenance mode, writes are rejected until it is disabled");
+    }
+
+    let state = AppState {
+        db,
+        base_url,
+        link_cache,
+        link_cache_ttl,
+        redis,
+        blocklist,
+        id_length,
+        link_id_blacklist: Arc::new(link_id_blacklist),
+        started_at: Instant::now(),
+        http_client: reqwest::Client::new(),
+        signing_secret,
+        signed_url_ttl,
+        redirect_robots_tag,
+        preview_cache_ttl,
+        super_admin_api_key,
+        config,
+        maintenance_mode,
+    };
+
+    // Durations are exported as histograms instead of summaries, so that percentiles
+    // can be aggregated across replicas
+    let (prometheus_layer, metric_handle) = PrometheusMetricLayerBuilder::new()
+        .with_metrics_from_fn(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(
+                    Matcher::Full(AXUM_HTTP_REQUESTS_DURATION_SECONDS.to_string()),
+                    SECONDS_DURATION_BUCKETS,
+                )
+                .and_then(|builder| {
+                    builder.set_buckets_for_metric(
+                        Matcher::Full("db_query_duration_seconds".to_string()),
+                        SECONDS_DURATION_BUCKETS,
+                    )
+                })
+                .expect("The duration buckets should never be empty")
+                .install_recorder()
+                .expect("Could not install the metrics recorder")
+        })
+        .build_pair();
+
+    let create_rate_limit_per_min = std::env::var("RATE_LIMIT_CREATE_PER_MIN")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(10);
+
+    let redirect_rate_limit_per_min = std::env::var("RATE_LIMIT_REDIRECT_PER_MIN")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(1000);
+
+    let create_rate_limiter = Arc::new(RateLimiter::new(create_rate_limit_per_min));
+    let redirect_rate_limiter = Arc::new(RateLimiter::new(redirect_rate_limit_per_min));
+
+    for rate_limiter in [create_rate_limiter.clone(), redirect_rate_limiter.clone()] {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+            loop {
+                interval.tick().await;
+                rate_limiter.purge_full_buckets();
+            }
+        });
+    }
+
+    let metrics_refresh_interval_secs = std::env::var("METRICS_REFRESH_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(30);
+
+    let gauges_state = state.clone();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(metrics_refresh_interval_secs));
+
+        loop {
+            interval.tick().await;
+            refresh_gauges(&gauges_state.db, gauges_state.config.db_query_timeout).await;
+        }
+    });
+
+    let purge_interval_secs = std::env::var("PURGE_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(86400);
+
+    let purge_state = state.clone();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(purge_interval_secs));
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+
+        // The shutdown signal is only checked between purges, so a running purge is always finished
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    purge_expired_links(&purge_state.db, purge_state.config.db_query_timeout).await;
+                    purge_expired_idempotency_keys(&purge_state.db, purge_state.config.db_query_timeout).await;
+                    complete_key_rotations(&purge_state.db, purge_state.config.db_query_timeout).await;
+                }
+                _ = &mut shutdown => {
+                    tracing::debug!("Stopped purging expired links");
+
+                    break;
+                }
+            }
+        }
+    });
+
+    let cors_allowed_origins = std::env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| "*".into());
+
+    let cors_allow_credentials = std::env::var("CORS_ALLOW_CREDENTIALS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(false);
+
+    let cors_allowed_origins = if cors_allowed_origins.trim() == "*" {
+        if cors_allow_credentials {
+            return Err("CORS_ALLOW_CREDENTIALS requires CORS_ALLOWED_ORIGINS to list explicit origins".into());
+        }
+
+        AllowOrigin::any()
+    } else {
+        let origins = cors_allowed_origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(HeaderValue::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        AllowOrigin::list(origins)
+    };
+
+    let cors_layer = CorsLayer::new()
+        .allow_origin(cors_allowed_origins)
+        .allow_credentials(cors_allow_credentials)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+        .allow_headers([CONTENT_TYPE, HeaderName::from_static("x-api-key"), HeaderName::from_static(IDEMPOTENCY_KEY_HEADER)])
+        // Browser clients can only back off if they are allowed to read the quota
+        .expose_headers([
+            HeaderName::from_static(RATE_LIMIT_LIMIT_HEADER),
+            HeaderName::from_static(RATE_LIMIT_REMAINING_HEADER),
+            HeaderName::from_static(RATE_LIMIT_RESET_HEADER),
+            HeaderName::from_static(AUTHENTICATED_KEY_NAME_HEADER),
+            RETRY_AFTER,
+        ])
+        .max_age(Duration::from_secs(3600));
+
+    let compression_level = std::env::var("COMPRESSION_LEVEL")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| (0..=9).contains(value))
+        .unwrap_or(4);
+
+    // Bodies are buffered completely before they are parsed, so without a limit a single
+    // request could exhaust the memory. Imports of csv files need a lot more than links.
+    let max_request_body_bytes = std::env::var("MAX_REQUEST_BODY_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(16 * 1024);
+
+    let import_max_body_bytes = std::env::var("IMPORT_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(10 * 1024 * 1024);
+
+    let in_flight_requests = InFlightRequests::default();
+
+    let app = app(
+        state,
+        AppOptions {
+            prometheus_layer,
+            metric_handle,
+            create_rate_limiter,
+            redirect_rate_limiter,
+            cors_layer,
+            compression_level,
+            max_request_body_bytes,
+            import_max_body_bytes,
+            in_flight_requests: in_flight_requests.clone(),
+        },
+    );
+
+
+    // TLS is only terminated here if both paths are set, otherwise a reverse proxy is expected to do it
+    let tls_paths = match (std::env::var("TLS_CERT_PATH"), std::env::var("TLS_KEY_PATH")) {
+        (Ok(cert_path), Ok(key_path)) => Some((PathBuf::from(cert_path), PathBuf::from(key_path))),
+        (Err(_), Err(_)) => None,
+        _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must either both be set or both be unset".into()),
+    };
+
+    let tls_config = match &tls_paths {
+        Some((cert_path, key_path)) => Some(
+            RustlsConfig::from_pem_file(cert_path, key_path)
+                .await
+                .map_err(|err| format!("Could not load the TLS certificate: {}", err))?,
+        ),
+        None => None,
+    };
+
+    // The watcher stops watching as soon as it gets dropped, so it has to live as long as the server
+    let _tls_watcher = tls_config
+        .as_ref()
+        .zip(tls_paths.as_ref())
+        .map(|(config, (cert_path, key_path))| tls::watch(config.clone(), cert_path, key_path))
+        .transpose()?;
+
+    let bind_host = std::env::var("BIND_HOST").unwrap_or_else(|_| "0.0.0.0".into());
+
+    let bind_port: u16 = match std::env::var("BIND_PORT") {
+        Ok(value) => value
+            .parse()
+            .ok()
+            .filter(|port| *port > 0)
+            .ok_or_else(|| format!("BIND_PORT must be between 1 and 65535, got {}", value))?,
+        Err(_) if tls_config.is_some() => 443,
+        Err(_) => 3000,
+    };
+
+    let listener = tokio::net::TcpListener::bind((bind_host.as_str(), bind_port))
         .await
         .expect("Could not initialize TcpListener");
 
-    tracing::debug!(
+    tracing::info!(
         "listening on {}",
         listener
         .local_addr()
         .expect("Could not convert listener address to local address")
     );
 
-    axum::serve(listener, app)
-        .await
-        .expect("Could not successfully create server");
+    let shutdown_timeout_secs = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(30);
+
+    let (shutdown_sender, mut shutdown_receiver) = tokio::sync::watch::channel(false);
+    let draining_requests = in_flight_requests.clone();
+
+    let graceful_shutdown = async move {
+        shutdown_signal().await;
+
+        tracing::info!(
+            "Shutdown signal received, draining {} in-flight requests",
+            draining_requests.count()
+        );
+
+        let _ = shutdown_sender.send(true);
+    };
+
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    let server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> = match tls_config {
+        Some(tls_config) => {
+            let http_port: u16 = std::env::var("HTTP_REDIRECT_PORT")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(80);
+
+            let http_listener = tokio::net::TcpListener::bind((bind_host.as_str(), http_port))
+                .await
+                .expect("Could not initialize TcpListener for the HTTP redirect");
+
+            tracing::info!(
+                "redirecting plain HTTP from {} to HTTPS",
+                http_listener
+                .local_addr()
+                .expect("Could not convert listener address to local address")
+            );
+
+            let redirect_app = Router::new().fallback(move |req: Request| tls::redirect_to_https(bind_port, req));
+            let mut redirect_shutdown_receiver = shutdown_receiver.clone();
+
+            tokio::spawn(async move {
+                let result = axum::serve(http_listener, redirect_app)
+                    .with_graceful_shutdown(async move {
+                        let _ = redirect_shutdown_receiver.wait_for(|shutting_down| *shutting_down).await;
+                    })
+                    .await;
+
+                if let Err(err) = result {
+                    tracing::error!("The HTTP redirect listener failed: {}", err);
+                }
+            });
+
+            let handle = axum_server::Handle::new();
+            let shutdown_handle = handle.clone();
+
+            tokio::spawn(async move {
+                graceful_shutdown.await;
+                shutdown_handle.graceful_shutdown(None);
+            });
+
+            Box::pin(
+                axum_server::from_tcp_rustls(listener.into_std()?, tls_config)
+                    .handle(handle)
+                    .serve(make_service),
+            )
+        }
+        None => Box::pin(
+            axum::serve(listener, make_service)
+                .with_graceful_shutdown(graceful_shutdown)
+                .into_future(),
+        ),
+    };
+
+    let shutdown_deadline = async {
+        let _ = shutdown_receiver.wait_for(|shutting_down| *shutting_down).await;
+        tokio::time::sleep(Duration::from_secs(shutdown_timeout_secs)).await;
+    };
+
+    tokio::select! {
+        result = server => {
+            result.expect("Could not successfully create server");
+
+            tracing::info!("Drained all in-flight requests, shutting down");
+        }
+        _ = shutdown_deadline => {
+            tracing::warn!(
+                "Shutdown timeout of {} seconds elapsed, dropping {} remaining requests",
+                shutdown_timeout_secs,
+                in_flight_requests.count()
+            );
+        }
+    }
+
+    // Flushes the spans that are still waiting for the next export batch
+    opentelemetry::global::shutdown_tracer_provider();
 
     Ok(())
 }
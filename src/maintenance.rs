@@ -0,0 +1,136 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::error::{AppError, ErrorBody};
+
+const MAINTENANCE_PATH_PREFIX: &str = "/admin/maintenance/";
+
+pub(crate) const RETRY_AFTER_SECS: u64 = 600;
+
+/// Whether the service only answers reads, e.g. while the database is being maintained.
+/// It is kept in memory, so every instance has to be switched on its own.
+#[derive(Clone)]
+pub struct MaintenanceMode(Arc<AtomicBool>);
+
+impl MaintenanceMode {
+    pub fn new(enabled: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::SeqCst);
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+}
+
+pub(crate) fn maintenance_error() -> AppError {
+    AppError::ServiceUnavailable(ErrorBody::new(
+        "maintenance_mode",
+        "The service is in maintenance and only answers reads",
+    ))
+}
+
+/// Rejects every write while maintenance mode is enabled, so that writes fail right
+/// away instead of timing out. The maintenance endpoints stay writable to switch it off.
+/// GraphQL sends queries and mutations alike as POST requests, so its mutations check
+/// the mode themselves.
+pub async fn reject_writes_during_maintenance(
+    State(maintenance_mode): State<MaintenanceMode>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+    #[cfg(feature = "graphql")]
+    let is_graphql = req.uri().path() == crate::graphql::GRAPHQL_PATH;
+    #[cfg(not(feature = "graphql"))]
+    let is_graphql = false;
+
+    if is_read
+        || is_graphql
+        || !maintenance_mode.is_enabled()
+        || req.uri().path().starts_with(MAINTENANCE_PATH_PREFIX)
+    {
+        return next.run(req).await;
+    }
+
+    tracing::debug!("Rejected {} {} during maintenance", req.method(), req.uri());
+
+    ([(RETRY_AFTER, RETRY_AFTER_SECS.to_string())], maintenance_error()).into_response()
+}
+
+/// The peer address is checked instead of forwarded headers, which clients can set
+/// to anything, so that only operators on the host itself can switch the mode. It is
+/// checked on top of the global api key, which `admin_auth` requires.
+fn require_local(peer: SocketAddr) -> Result<(), AppError> {
+    if peer.ip().is_loopback() {
+        return Ok(());
+    }
+
+    tracing::warn!("Refused to switch maintenance mode for non-local peer {}", peer.ip());
+
+    Err(AppError::Forbidden(ErrorBody::new("not_local", "Only local requests may switch maintenance mode")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance/enable",
+    responses(
+        (status = 200, description = "Maintenance mode enabled, writes are rejected", body = MaintenanceStatus),
+        (status = 401, description = "Not authenticated with the global api key"),
+        (status = 403, description = "Request does not come from the host itself")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn enable_maintenance_mode(
+    State(maintenance_mode): State<MaintenanceMode>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+) -> Result<Json<MaintenanceStatus>, AppError> {
+    require_local(peer)?;
+
+    maintenance_mode.set(true);
+
+    tracing::warn!("Enabled maintenance mode, writes are rejected until it is disabled");
+
+    Ok(Json(MaintenanceStatus { enabled: true }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance/disable",
+    responses(
+        (status = 200, description = "Maintenance mode disabled, writes are accepted again", body = MaintenanceStatus),
+        (status = 401, description = "Not authenticated with the global api key"),
+        (status = 403, description = "Request does not come from the host itself")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn disable_maintenance_mode(
+    State(maintenance_mode): State<MaintenanceMode>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+) -> Result<Json<MaintenanceStatus>, AppError> {
+    require_local(peer)?;
+
+    maintenance_mode.set(false);
+
+    tracing::warn!("Disabled maintenance mode, writes are accepted again");
+
+    Ok(Json(MaintenanceStatus { enabled: false }))
+}
@@ -0,0 +1,227 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use chrono::Utc;
+use metrics::increment_counter;
+use regex::Regex;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Client;
+use url::Url;
+
+use crate::auth::AuthenticatedApiKey;
+use crate::error::{AppError, ErrorBody};
+use crate::state::AppState;
+use crate::utils::{internal_error, timed_query};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Open Graph tags are part of the head, so there is no need to read huge pages to the end
+const MAX_PAGE_BYTES: usize = 512 * 1024;
+
+const MAX_PREVIEW_FIELD_LENGTH: usize = 1024;
+
+/// What the target page tells about itself through its Open Graph tags
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkPreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Relative urls of the page are resolved against the target url
+    pub image_url: Option<String>,
+}
+
+struct StoredPreview {
+    target_url: String,
+    preview_metadata: Option<sqlx::types::Json<LinkPreview>>,
+    preview_fetched_at: Option<chrono::DateTime<Utc>>,
+}
+
+fn meta_tag_regex() -> &'static Regex {
+    static META_TAG_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    META_TAG_REGEX.get_or_init(|| Regex::new(r"(?is)<meta\s[^>]*>").expect("The meta tag regex should always compile"))
+}
+
+fn attribute_regex() -> &'static Regex {
+    static ATTRIBUTE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    ATTRIBUTE_REGEX.get_or_init(|| {
+        Regex::new(r#"(?s)([a-zA-Z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+            .expect("The attribute regex should always compile")
+    })
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Reads the Open Graph tags of a page. Pages are rarely valid html, so the meta tags
+/// are picked out one by one instead of parsing the whole document. Some pages use
+/// `name` instead of `property`, which is accepted as well.
+fn parse_preview(html: &str, page_url: &Url) -> LinkPreview {
+    let mut preview = LinkPreview::default();
+
+    for meta_tag in meta_tag_regex().find_iter(html) {
+        let mut property = None;
+        let mut content = None;
+
+        for attribute in attribute_regex().captures_iter(meta_tag.as_str()) {
+            let value = attribute.get(2).or_else(|| attribute.get(3)).map_or("", |value| value.as_str());
+
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "property" | "name" => property = Some(value.to_ascii_lowercase()),
+                "content" => content = Some(decode_entities(value.trim())),
+                _ => {}
+            }
+        }
+
+        let (Some(property), Some(content)) = (property, content) else {
+            continue;
+        };
+
+        if content.is_empty() || content.chars().count() > MAX_PREVIEW_FIELD_LENGTH {
+            continue;
+        }
+
+        // The first occurrence wins, like it does for the crawlers of social networks
+        match property.as_str() {
+            "og:title" if preview.title.is_none() => preview.title = Some(content),
+            "og:description" if preview.description.is_none() => preview.description = Some(content),
+            "og:image" if preview.image_url.is_none() => {
+                preview.image_url = page_url.join(&content).ok().map(String::from);
+            }
+            _ => {}
+        }
+    }
+
+    preview
+}
+
+async fn fetch_preview(client: &Client, target_url: &str) -> Result<LinkPreview, Box<dyn std::error::Error>> {
+    let mut response = client
+        .get(target_url)
+        .timeout(FETCH_TIMEOUT)
+        .header(ACCEPT, "text/html")
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html") || value.starts_with("application/xhtml+xml"));
+
+    // Images, videos and the like have no tags to read, so they get an empty preview
+    if !is_html {
+        return Ok(LinkPreview::default());
+    }
+
+    // Redirects are followed, relative image urls belong to the page they led to
+    let page_url = response.url().clone();
+
+    let mut page = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        page.extend_from_slice(&chunk);
+
+        if page.len() >= MAX_PAGE_BYTES {
+            page.truncate(MAX_PAGE_BYTES);
+            break;
+        }
+    }
+
+    Ok(parse_preview(&String::from_utf8_lossy(&page), &page_url))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/preview",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Title, description, and image of the target page, empty if it has none", body = LinkPreview),
+        (status = 404, description = "Link not found"),
+        (status = 502, description = "Target page could not be fetched")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_link_preview(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<Json<LinkPreview>, AppError> {
+    let query_timeout = state.config.db_query_timeout;
+
+    let stored_preview = timed_query(
+        "select_link_preview",
+        query_timeout,
+        sqlx::query_as!(
+            StoredPreview,
+            r#"
+            select target_url, preview_metadata as "preview_metadata: sqlx::types::Json<LinkPreview>", preview_fetched_at
+            from links where id = $1 and deleted_at is null and workspace_id is not distinct from $2
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_optional(&state.db)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?
+    .ok_or_else(|| AppError::NotFound(ErrorBody::new("link_not_found", "Not found")))?;
+
+    // A preview fetched in the future according to the clock of this host is still fresh
+    let is_fresh = stored_preview.preview_fetched_at.is_some_and(|fetched_at| {
+        (Utc::now() - fetched_at)
+            .to_std()
+            .map_or(true, |age| age < state.preview_cache_ttl)
+    });
+
+    if let (true, Some(sqlx::types::Json(preview))) = (is_fresh, stored_preview.preview_metadata) {
+        tracing::debug!("Served cached preview of link with id {}", link_id);
+
+        return Ok(Json(preview));
+    }
+
+    let preview = fetch_preview(&state.http_client, &stored_preview.target_url)
+        .await
+        .map_err(|err| {
+            tracing::warn!("Fetching the preview of link with id {} failed: {}", link_id, err);
+            increment_counter!("preview_fetch_failures_total");
+
+            AppError::BadGateway(ErrorBody::new("preview_unavailable", "The target page could not be fetched"))
+        })?;
+
+    // The target may have changed while it was fetched, in which case the preview is not kept
+    timed_query(
+        "update_link_preview",
+        query_timeout,
+        sqlx::query!(
+            r#"
+            update links set preview_metadata = $3, preview_fetched_at = now()
+            where id = $1 and target_url = $2
+            "#,
+            &link_id,
+            &stored_preview.target_url,
+            sqlx::types::Json(&preview) as sqlx::types::Json<&LinkPreview>
+        )
+        .execute(&state.db)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Fetched preview of link with id {}", link_id);
+
+    Ok(Json(preview))
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use metrics::counter;
+use sqlx::PgPool;
+
+use crate::utils::timed_query;
+
+/// Deletes all links whose expiry has passed. Their statistics are removed by
+/// the cascading foreign key. Failures are logged and retried with the next run.
+pub async fn purge_expired_links(pool: &PgPool, query_timeout: Duration) {
+    let purged_links = timed_query(
+        "delete_expired_links",
+        query_timeout,
+        sqlx::query!("delete from links where expires_at < now() and expires_at is not null")
+            .execute(pool)
+    )
+    .await;
+
+    match purged_links {
+        Ok(Ok(purged_links)) => {
+            tracing::info!("Purged {} expired links", purged_links.rows_affected());
+            counter!("purged_links_total", purged_links.rows_affected());
+        }
+        Ok(Err(err)) => tracing::error!("Purging expired links failed with the following error: {}", err),
+        Err(elapsed) => tracing::error!("Purging expired links resulted in a timeout: {}", elapsed),
+    }
+}
+
+/// Idempotency keys are only honored for 24 hours, after which they are deleted.
+pub async fn purge_expired_idempotency_keys(pool: &PgPool, query_timeout: Duration) {
+    let purged_keys = timed_query(
+        "delete_expired_idempotency_keys",
+        query_timeout,
+        sqlx::query!("delete from idempotency_keys where created_at <= now() - interval '24 hours'")
+            .execute(pool)
+    )
+    .await;
+
+    match purged_keys {
+        Ok(Ok(purged_keys)) => tracing::info!("Purged {} expired idempotency keys", purged_keys.rows_affected()),
+        Ok(Err(err)) => tracing::error!("Purging expired idempotency keys failed with the following error: {}", err),
+        Err(elapsed) => tracing::error!("Purging expired idempotency keys resulted in a timeout: {}", elapsed),
+    }
+}
+
+/// Revokes the old keys of all rotations whose grace period has ended. Authentication
+/// already rejects them once it ends, this only records the completed rotation.
+pub async fn complete_key_rotations(pool: &PgPool, query_timeout: Duration) {
+    let rotated_keys = timed_query(
+        "complete_key_rotations",
+        query_timeout,
+        sqlx::query_scalar!(
+            r#"
+            update api_keys set revoked_at = grace_period_ends_at
+            where grace_period_ends_at <= now() and revoked_at is null
+            returning id
+            "#
+        )
+            .fetch_all(pool)
+    )
+    .await;
+
+    match rotated_keys {
+        Ok(Ok(rotated_keys)) => {
+            for rotated_key in rotated_keys {
+                tracing::info!(event = "key_rotation_completed", "Completed rotating api key with id {}", rotated_key);
+            }
+        }
+        Ok(Err(err)) => tracing::error!("Completing key rotations failed with the following error: {}", err),
+        Err(elapsed) => tracing::error!("Completing key rotations resulted in a timeout: {}", elapsed),
+    }
+}
@@ -0,0 +1,160 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use dashmap::DashMap;
+use metrics::increment_counter;
+
+use crate::error::ErrorBody;
+
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "x-ratelimit-limit";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+pub const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// What is left of a bucket after a request took a token from it, or failed to.
+struct Quota {
+    remaining: u32,
+    /// Time until the bucket is completely refilled
+    reset_after: Duration,
+}
+
+/// Keeps the code of every other error body, the other fields tell clients when to retry.
+#[derive(serde::Serialize)]
+struct RateLimitExceeded {
+    #[serde(flatten)]
+    body: ErrorBody,
+    error: &'static str,
+    retry_after_secs: u64,
+}
+
+pub struct RateLimiter {
+    requests_per_minute: u32,
+    buckets: DashMap<IpAddr, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+            buckets: DashMap::new(),
+        }
+    }
+
+    fn refill_rate_per_sec(&self) -> f64 {
+        f64::from(self.requests_per_minute) / 60.0
+    }
+
+    /// Takes one token from the bucket of the given ip. When the bucket is exhausted,
+    /// the time until the next token becomes available is returned as well.
+    fn try_acquire(&self, ip: IpAddr) -> Result<Quota, (Quota, Duration)> {
+        let capacity = f64::from(self.requests_per_minute);
+        let refill_rate_per_sec = self.refill_rate_per_sec();
+        let now = Instant::now();
+
+        let mut bucket = self.buckets.entry(ip).or_insert_with(|| Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_rate_per_sec).min(capacity);
+        bucket.last_refill = now;
+
+        let acquired = bucket.tokens >= 1.0;
+
+        if acquired {
+            bucket.tokens -= 1.0;
+        }
+
+        let quota = Quota {
+            remaining: bucket.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64((capacity - bucket.tokens) / refill_rate_per_sec),
+        };
+
+        if acquired {
+            return Ok(quota);
+        }
+
+        Err((quota, Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / refill_rate_per_sec,
+        )))
+    }
+
+    /// Takes one token for requests that only count in part, like the mutations among
+    /// GraphQL queries. Returns the time until the next token when there is none.
+    pub fn acquire(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.try_acquire(ip).map(|_| ()).map_err(|(_, retry_after)| retry_after)
+    }
+
+    /// Tells clients about their quota, so that they can back off before they get throttled.
+    fn insert_headers(&self, headers: &mut HeaderMap, quota: &Quota) {
+        let reset_at = Utc::now().timestamp() + quota.reset_after.as_secs_f64().ceil() as i64;
+
+        headers.insert(RATE_LIMIT_LIMIT_HEADER, HeaderValue::from(self.requests_per_minute));
+        headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from(quota.remaining));
+        headers.insert(RATE_LIMIT_RESET_HEADER, HeaderValue::from(reset_at));
+    }
+
+    /// Removes all buckets that have been refilled completely, as they are
+    /// indistinguishable from a freshly created one.
+    pub fn purge_full_buckets(&self) {
+        let capacity = f64::from(self.requests_per_minute);
+        let refill_rate_per_sec = self.refill_rate_per_sec();
+        let now = Instant::now();
+
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+
+            bucket.tokens + elapsed * refill_rate_per_sec < capacity
+        });
+    }
+}
+
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let quota = match limiter.try_acquire(addr.ip()) {
+        Ok(quota) => quota,
+        Err((quota, retry_after)) => {
+            let labels = [("uri", format!("{}!", req.uri()))];
+
+            tracing::warn!("Rate limit exceeded for {}", addr.ip());
+            increment_counter!("rate_limited_calls_count", &labels);
+
+            let retry_after_secs = retry_after.as_secs_f64().ceil() as u64;
+
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                [("Retry-After", retry_after_secs.to_string())],
+                Json(RateLimitExceeded {
+                    body: ErrorBody::new("rate_limited", "Too many requests"),
+                    error: "rate_limit_exceeded",
+                    retry_after_secs,
+                }),
+            )
+                .into_response();
+            limiter.insert_headers(response.headers_mut(), &quota);
+
+            return Err(response);
+        }
+    };
+
+    let mut response = next.run(req).await;
+    limiter.insert_headers(response.headers_mut(), &quota);
+
+    Ok(response)
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use deadpool_redis::redis::AsyncCommands;
+use deadpool_redis::Pool;
+use metrics::increment_counter;
+
+use crate::routes::Link;
+
+// Redis is only a cache shared between replicas, so every failure is logged and
+// treated like a miss instead of failing the request. Postgres stays the source
+// of truth.
+
+fn key(link_id: &str) -> String {
+    format!("link:{}", link_id)
+}
+
+pub async fn get_link(pool: &Pool, link_id: &str) -> Option<Link> {
+    let result: Result<Option<String>, String> = async {
+        let mut connection = pool.get().await.map_err(|err| err.to_string())?;
+
+        connection.get(key(link_id)).await.map_err(|err| err.to_string())
+    }
+    .await;
+
+    match result {
+        Ok(Some(value)) => match serde_json::from_str(&value) {
+            Ok(link) => Some(link),
+            Err(err) => {
+                tracing::error!("Deserializing the cached link with id {} failed: {}", link_id, err);
+
+                None
+            }
+        },
+        Ok(None) => None,
+        Err(err) => {
+            tracing::error!("Fetching the link with id {} from redis failed: {}", link_id, err);
+            increment_counter!("redis_errors_total");
+
+            None
+        }
+    }
+}
+
+pub async fn set_link(pool: &Pool, link: &Link, ttl: Duration) {
+    let result: Result<(), String> = async {
+        let value = serde_json::to_string(link).map_err(|err| err.to_string())?;
+        let mut connection = pool.get().await.map_err(|err| err.to_string())?;
+
+        connection
+            .set_ex(key(&link.id), value, ttl.as_secs())
+            .await
+            .map_err(|err| err.to_string())
+    }
+    .await;
+
+    if let Err(err) = result {
+        tracing::error!("Caching the link with id {} in redis failed: {}", link.id, err);
+        increment_counter!("redis_errors_total");
+    }
+}
+
+pub async fn invalidate_link(pool: &Pool, link_id: &str) {
+    let result: Result<(), String> = async {
+        let mut connection = pool.get().await.map_err(|err| err.to_string())?;
+
+        connection.del(key(link_id)).await.map_err(|err| err.to_string())
+    }
+    .await;
+
+    if let Err(err) = result {
+        tracing::error!("Invalidating the link with id {} in redis failed: {}", link_id, err);
+        increment_counter!("redis_errors_total");
+    }
+}
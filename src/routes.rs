@@ -1,229 +1,3008 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::Duration;
+
 use axum::body::Body;
-use axum::extract::{Path, State};
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::header::{ACCEPT, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED, USER_AGENT, VARY};
 use axum::http::{HeaderMap, StatusCode};
-use axum::Json;
+use axum::{Extension, Json};
 use axum::response::{IntoResponse, Response};
-use base64::Engine;
 use base64::engine::general_purpose;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use image::{DynamicImage, ImageOutputFormat, Luma};
 use metrics::increment_counter;
-use rand::Rng;
-use sqlx::{Error, PgPool};
+use nanoid::nanoid;
+use qrcode::QrCode;
+use regex::Regex;
+use sha3::{Digest, Sha3_256};
+use sqlx::{Connection, Error, PgConnection, PgPool, Postgres, Transaction};
 use sqlx::error::ErrorKind;
+use sqlx::postgres::{PgArguments, PgDatabaseError};
 use url::Url;
+use validator::Validate;
+use woothee::parser::Parser as UserAgentParser;
+use woothee::woothee::VALUE_UNKNOWN;
+use uuid::Uuid;
+
+use crate::audit;
+use crate::auth::AuthenticatedApiKey;
+use crate::blocklist::Blocklist;
+use crate::config::Config;
+use crate::error::{AppError, ErrorBody, KeepServerErrorBody};
+use crate::extract::{JsonOrForm, OptionalJson};
+use crate::idempotency;
+use crate::redis_cache;
+use crate::signing;
+use crate::state::AppState;
+use crate::utils::{body_validation_error, internal_error, timed_query, validation_error, ValidatedJson};
+use crate::webhook::{ClickEvent, notify_click};
+
+const ID_ALPHABET: [char; 62] = [
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
+    'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M',
+    'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+];
+
+pub const DEFAULT_ID_LENGTH: usize = 8;
+
+pub const MIN_ID_LENGTH: usize = 4;
+
+pub const DEFAULT_LINK_ID_BLACKLIST: &str = "health,metrics,create,admin,docs,openapi";
+
+pub const MAX_ID_LENGTH: usize = 21;
+
+const ALLOWED_URL_SCHEMES: [&str; 2] = ["http", "https"];
+
+const DEFAULT_BULK_MAX_LINKS: usize = 100;
+
+const QR_CODE_CACHE_CONTROL_HEADER_VALUE: &str = "public, max-age=86400";
+
+const DEFAULT_QR_CODE_SIZE: u32 = 256;
+
+const MIN_QR_CODE_SIZE: u32 = 100;
+
+const MAX_QR_CODE_SIZE: u32 = 1000;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+const MAX_PAGE_SIZE: u32 = 500;
+
+const MAX_LOCALE_LENGTH: usize = 64;
+
+const MAX_TAG_LENGTH: usize = 64;
+const MAX_ROBOTS_TAG_LENGTH: usize = 255;
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+const MIN_AUTO_EXPIRES_IN_SECS: u64 = 60;
+const MAX_AUTO_EXPIRES_IN_SECS: u64 = 31_536_000;
+
+const DEFAULT_CACHE_CONTROL_HEADER_VALUE: &str =
+    "public, max-age=300, s-maxage=300, stale-while-revalidate=300, stale-if-error=300";
+
+const X_ROBOTS_TAG: &str = "x-robots-tag";
+
+pub const DEFAULT_REDIRECT_ROBOTS_TAG: &str = "noindex, nofollow";
+
+// Cached redirects of signed links would outlive the expiry of their signature
+const SIGNED_CACHE_CONTROL_HEADER_VALUE: &str = "private, no-store";
+
+const CACHE_CONTROL_DIRECTIVES: [&str; 8] = [
+    "public",
+    "private",
+    "no-cache",
+    "no-store",
+    "no-transform",
+    "must-revalidate",
+    "proxy-revalidate",
+    "immutable",
+];
+
+const CACHE_CONTROL_DIRECTIVES_WITH_SECONDS: [&str; 4] = ["max-age", "s-maxage", "stale-while-revalidate", "stale-if-error"];
+
+const MAX_UTM_PARAM_NAME_LENGTH: usize = 64;
+
+/// Stored as jsonb, which sqlx only maps to a map through its json wrapper
+pub type UtmParams = sqlx::types::Json<HashMap<String, String>>;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    pub id: String,
+    pub target_url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Permanent links are redirected to with a 301 instead of a 307. Browsers cache
+    /// 301s, often indefinitely, and will not ask again before the user clears their
+    /// cache, so updating the target of a permanent link may never reach visitors
+    /// who already followed it.
+    #[serde(rename = "permanent")]
+    #[cfg_attr(feature = "graphql", graphql(name = "permanent"))]
+    pub is_permanent: bool,
+    /// Total number of clicks after which the link stops redirecting
+    pub max_clicks: Option<i64>,
+    /// Url that receives a POST request for every click on the link
+    pub webhook_url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Cache-Control header of redirects, the global default when empty
+    #[serde(default)]
+    pub cache_control: Option<String>,
+    /// X-Robots-Tag header of redirects, the global default when empty
+    #[serde(default)]
+    pub robots_tag: Option<String>,
+    /// Query parameters added to the target url of redirects, unless it already has them
+    #[serde(default)]
+    #[schema(value_type = Option<HashMap<String, String>>)]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub utm_params: Option<UtmParams>,
+    /// Signed links only redirect for requests carrying a valid, unexpired signature
+    #[serde(default)]
+    pub signed: bool,
+    /// Deactivated links do not redirect, but keep their statistics
+    pub active: bool,
+    /// Sorted by name
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct ShortLink {
+    #[serde(flatten)]
+    #[cfg_attr(feature = "graphql", graphql(flatten))]
+    pub link: Link,
+    pub short_url: String,
+    /// Only returned when a signed link gets created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_url: Option<String>,
+}
+
+/// Only returned by the info endpoint, as it is only relevant for audits
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct LinkInfo {
+    #[serde(flatten)]
+    #[cfg_attr(feature = "graphql", graphql(flatten))]
+    pub link: Link,
+    /// User agent of the client that created the link
+    pub creator_user_agent: Option<String>,
+    /// Id of the api key the link was created with, empty for the global key
+    pub created_by_key_id: Option<Uuid>,
+    /// Id of the link this alias follows, empty for links that are no alias
+    pub canonical_id: Option<String>,
+    /// Ids of the aliases following this link, sorted
+    pub aliases: Vec<String>,
+}
+
+/// Who created a link, kept for audit trails. The link belongs to the workspace of the key.
+struct LinkCreator {
+    user_agent: Option<String>,
+    api_key_id: Option<Uuid>,
+    workspace_id: Option<Uuid>,
+}
+
+impl LinkCreator {
+    fn new(headers: &HeaderMap, api_key: AuthenticatedApiKey) -> Self {
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Self {
+            user_agent,
+            api_key_id: api_key.id,
+            workspace_id: api_key.workspace_id,
+        }
+    }
+}
+
+impl ShortLink {
+    pub fn new(link: Link, base_url: &str) -> Self {
+        let short_url = format!("{}/{}", base_url, link.id);
+
+        Self { link, short_url, signed_url: None }
+    }
+}
+
+// Single links carry their last modification, so that clients can send it back as
+// If-Unmodified-Since when updating them
+impl IntoResponse for Link {
+    fn into_response(self) -> Response {
+        ([(LAST_MODIFIED, http_date(self.updated_at))], Json(self)).into_response()
+    }
+}
+
+impl IntoResponse for LinkInfo {
+    fn into_response(self) -> Response {
+        ([(LAST_MODIFIED, http_date(self.link.updated_at))], Json(self)).into_response()
+    }
+}
+
+impl IntoResponse for ShortLink {
+    fn into_response(self) -> Response {
+        ([(LAST_MODIFIED, http_date(self.link.updated_at))], Json(self)).into_response()
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Validate)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::InputObject))]
+#[cfg_attr(feature = "graphql", graphql(name = "LinkTargetInput"))]
+#[serde(rename_all = "camelCase")]
+pub struct LinkTarget {
+    #[validate(url(message = "url malformed"))]
+    pub target_url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub permanent: Option<bool>,
+    pub max_clicks: Option<i64>,
+    pub webhook_url: Option<String>,
+    #[validate(length(max = 255, message = "title must be at most 255 characters long"))]
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Cache-Control header of redirects made of known directives, like `no-cache` or
+    /// `max-age=86400`. Permanent links must not use `no-store`.
+    pub cache_control: Option<String>,
+    /// X-Robots-Tag header of redirects, like `all` to let search engines index a link
+    /// of a deployment that keeps them from indexing its links by default
+    pub robots_tag: Option<String>,
+    /// Replaces all tags of the link, leaving it out keeps them when updating
+    pub tags: Option<Vec<String>>,
+    /// Query parameters like `utm_source` that redirects add to the target url, those
+    /// already part of the target url are kept
+    pub utm_params: Option<HashMap<String, String>>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Validate)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::InputObject))]
+#[cfg_attr(feature = "graphql", graphql(name = "CreateLinkInput"))]
+#[serde(rename_all = "camelCase")]
+pub struct CreateLinkRequest {
+    #[validate(url(message = "url malformed"))]
+    pub target_url: String,
+    pub custom_id: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Shorthand for `expiresAt` relative to now, between one minute and one year
+    pub auto_expires_in_secs: Option<u64>,
+    pub permanent: Option<bool>,
+    pub max_clicks: Option<i64>,
+    pub webhook_url: Option<String>,
+    #[validate(length(max = 255, message = "title must be at most 255 characters long"))]
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Cache-Control header of redirects, see `LinkTarget`
+    pub cache_control: Option<String>,
+    /// X-Robots-Tag header of redirects, see `LinkTarget`
+    pub robots_tag: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Query parameters that redirects add to the target url, see `LinkTarget`
+    pub utm_params: Option<HashMap<String, String>>,
+    /// Requires a signature for redirects and returns a signed url that is valid until
+    /// the link expires, or for SIGNED_URL_TTL_SECS if it does not expire
+    pub signed: Option<bool>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAliasRequest {
+    /// Generated like the ids of new links when left out
+    pub custom_id: Option<String>,
+}
+
+#[derive(Default, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyLinkRequest {
+    /// Generated like the ids of new links when left out
+    pub custom_id: Option<String>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SignatureQuery {
+    pub sig: Option<String>,
+    /// Unix timestamp the signature expires at. Taken as a string and only parsed when a
+    /// signature is verified, so that a stray `exp` can not break redirects of other links.
+    pub exp: Option<String>,
+}
+
+/// Returned instead of the redirect to clients that prefer json
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectTarget {
+    pub target_url: String,
+}
+
+/// How `redirect` answers, depending on what the client accepts
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RedirectFormat {
+    Redirect,
+    PlainText,
+    Json,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Ok,
+    Error,
+}
+
+/// Load balancers and readiness probes rely on these exact field names, which is
+/// why they are not camel cased like the rest of the api.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Health {
+    pub status: HealthStatus,
+    pub db: ComponentStatus,
+    pub uptime_secs: u64,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkLinkError {
+    pub index: usize,
+    pub error: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct CountedLinkStatistic {
+    pub link_id: String,
+    pub amount: Option<i64>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedStatistics {
+    pub items: Vec<CountedLinkStatistic>,
+    pub page: u32,
+    pub page_size: u32,
+    pub total_count: i64,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedLinks {
+    pub items: Vec<Link>,
+    pub page: u32,
+    pub page_size: u32,
+    pub total_count: i64,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListLinksQuery {
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+    pub q: Option<String>,
+    /// Only lists links carrying this tag
+    pub tag: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkStatisticsSummary {
+    pub total_clicks: i64,
+    pub unique_referers: i64,
+    pub unique_user_agents: i64,
+    pub first_click_at: Option<DateTime<Utc>>,
+    pub last_click_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IpStatistic {
+    pub ip_hash: String,
+    pub count: i64,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceStatistic {
+    pub browser: Option<String>,
+    pub os: Option<String>,
+    pub count: i64,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClickBucket {
+    pub bucket: DateTime<Utc>,
+    pub count: i64,
+}
+
+#[derive(Clone, Copy, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeseriesBucket {
+    Hour,
+    Day,
+    Week,
+}
+
+impl TimeseriesBucket {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TimeseriesBucket::Hour => "hour",
+            TimeseriesBucket::Day => "day",
+            TimeseriesBucket::Week => "week",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimeseriesQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub bucket: Option<TimeseriesBucket>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QrCodeQuery {
+    pub size: Option<u32>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    #[validate(range(min = 1, message = "page must be at least 1"))]
+    pub page: Option<u32>,
+    #[validate(range(min = 1, max = "MAX_PAGE_SIZE", message = "page size must be between 1 and 500"))]
+    pub page_size: Option<u32>,
+}
+
+impl Pagination {
+    /// Clients match on `invalid_pagination`, which is why it is kept over the code of `ValidatedJson`
+    pub(crate) fn resolve(&self) -> Result<(u32, u32), AppError> {
+        self.validate()
+            .map_err(|errors| validation_error("invalid_pagination", errors, str::to_owned))?;
+
+        Ok((self.page.unwrap_or(1), self.page_size.unwrap_or(DEFAULT_PAGE_SIZE)))
+    }
+}
+
+pub(crate) enum LinkInputError {
+    Malformed,
+    UnsupportedScheme,
+    Blocked,
+    InvalidWebhookUrl,
+    InvalidTag,
+    InvalidCacheControl,
+    NoStorePermanentLink,
+    InvalidUtmParam,
+    InvalidRobotsTag,
+}
+
+impl LinkInputError {
+    fn code(&self) -> &'static str {
+        match self {
+            LinkInputError::Malformed => "url_malformed",
+            LinkInputError::UnsupportedScheme => "unsupported_scheme",
+            LinkInputError::Blocked => "url_blocked",
+            LinkInputError::InvalidWebhookUrl => "invalid_webhook_url",
+            LinkInputError::InvalidTag => "invalid_tag",
+            LinkInputError::InvalidCacheControl => "invalid_cache_control",
+            LinkInputError::NoStorePermanentLink => "no_store_permanent_link",
+            LinkInputError::InvalidUtmParam => "invalid_utm_param",
+            LinkInputError::InvalidRobotsTag => "invalid_robots_tag",
+        }
+    }
+
+    pub(crate) fn message(&self) -> &'static str {
+        match self {
+            LinkInputError::Malformed => "url malformed",
+            LinkInputError::UnsupportedScheme => "only http and https schemes are allowed",
+            LinkInputError::Blocked => "target url is blocked",
+            LinkInputError::InvalidWebhookUrl => "webhook url must be a valid http or https url",
+            LinkInputError::InvalidTag => "tags must be between 1 and 64 characters long",
+            LinkInputError::InvalidCacheControl => "cache control must only contain known directives",
+            LinkInputError::NoStorePermanentLink => "permanent links must not use no-store",
+            LinkInputError::InvalidUtmParam => "utm param names must be between 1 and 64 characters long",
+            LinkInputError::InvalidRobotsTag => "robots tag must be printable ascii of at most 255 characters",
+        }
+    }
+}
+
+impl From<LinkInputError> for AppError {
+    fn from(err: LinkInputError) -> Self {
+        let body = ErrorBody::new(err.code(), err.message());
+
+        match err {
+            LinkInputError::Malformed => AppError::Conflict(body),
+            LinkInputError::UnsupportedScheme
+            | LinkInputError::Blocked
+            | LinkInputError::InvalidWebhookUrl
+            | LinkInputError::InvalidTag
+            | LinkInputError::InvalidCacheControl
+            | LinkInputError::NoStorePermanentLink
+            | LinkInputError::InvalidUtmParam
+            | LinkInputError::InvalidRobotsTag => AppError::UnprocessableEntity(body),
+        }
+    }
+}
+
+impl IntoResponse for LinkInputError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+/// Parses the target url of a link and makes sure that it can actually be
+/// redirected to. Everything besides http and https, like `javascript:` or
+/// `file://`, is nonsensical for a redirect and potentially dangerous. The same
+/// goes for hosts on the blocklist.
+pub(crate) fn parse_target_url(target_url: &str, blocklist: &Blocklist) -> Result<String, LinkInputError> {
+    let url = Url::parse(target_url).map_err(|_| LinkInputError::Malformed)?;
+
+    if !ALLOWED_URL_SCHEMES.contains(&url.scheme()) {
+        return Err(LinkInputError::UnsupportedScheme);
+    }
+
+    if blocklist.is_blocked(&url) {
+        return Err(LinkInputError::Blocked);
+    }
+
+    Ok(url.to_string())
+}
+
+fn parse_webhook_url(webhook_url: Option<&str>) -> Result<Option<String>, LinkInputError> {
+    let Some(webhook_url) = webhook_url else {
+        return Ok(None);
+    };
+
+    let url = Url::parse(webhook_url).map_err(|_| LinkInputError::InvalidWebhookUrl)?;
+
+    if !ALLOWED_URL_SCHEMES.contains(&url.scheme()) {
+        return Err(LinkInputError::InvalidWebhookUrl);
+    }
+
+    Ok(Some(url.to_string()))
+}
+
+/// Determines the ip of the visitor. Proxies in front of the service hide the
+/// real peer, so the headers they set take precedence over the peer address.
+fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    let header_value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let cf_connecting_ip = header_value("cf-connecting-ip");
+    let forwarded_for = header_value("x-forwarded-for").and_then(|value| value.split(',').next());
+
+    cf_connecting_ip
+        .and_then(|ip| ip.trim().parse().ok())
+        .or_else(|| forwarded_for.and_then(|ip| ip.trim().parse().ok()))
+        .unwrap_or_else(|| peer.ip())
+}
+
+/// Extracts the browser and operating system families, e.g. `Chrome` and `Windows 10`.
+/// Whatever woothee does not recognize is left empty instead of being stored as `UNKNOWN`.
+fn parse_user_agent(user_agent: Option<&str>) -> (Option<String>, Option<String>) {
+    let Some(parsed) = user_agent.and_then(|user_agent| UserAgentParser::new().parse(user_agent)) else {
+        return (None, None);
+    };
+
+    let known = |value: &str| (value != VALUE_UNKNOWN).then(|| value.to_owned());
+
+    (known(parsed.name), known(parsed.os))
+}
+
+/// Ips are personal data, so only their hash is ever persisted
+fn hash_ip(ip: IpAddr) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(ip.to_string().as_bytes());
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// The entity tag only depends on the target url, as that is all a client that
+/// already followed the redirect cares about
+fn entity_tag(link: &Link) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(redirect_target_url(link).as_bytes());
+
+    format!("\"{}\"", general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize()))
+}
+
+fn http_date(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Follows RFC 9110 in that If-Modified-Since is only considered when the
+/// client did not send If-None-Match.
+fn is_not_modified(headers: &HeaderMap, entity_tag: &str, last_modified: DateTime<Utc>) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        return if_none_match.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == entity_tag)
+        });
+    }
+
+    headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|if_modified_since| last_modified.timestamp() <= if_modified_since.timestamp())
+}
+
+/// Picks the media type with the highest quality the client accepts, earlier ones
+/// winning ties. Browsers, wildcards and clients that accept nothing we know of are
+/// redirected as before.
+fn redirect_format(headers: &HeaderMap) -> RedirectFormat {
+    let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return RedirectFormat::Redirect;
+    };
+
+    let mut preferred: Option<(RedirectFormat, f32)> = None;
+
+    for media_range in accept.split(',') {
+        let mut parts = media_range.split(';').map(str::trim);
+
+        let format = match parts.next().unwrap_or_default().to_ascii_lowercase().as_str() {
+            "text/plain" => RedirectFormat::PlainText,
+            "application/json" => RedirectFormat::Json,
+            "text/html" | "*/*" => RedirectFormat::Redirect,
+            _ => continue,
+        };
+
+        let quality = parts
+            .filter_map(|parameter| parameter.strip_prefix("q="))
+            .find_map(|quality| quality.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if quality > 0.0 && preferred.map_or(true, |(_, best)| quality > best) {
+            preferred = Some((format, quality));
+        }
+    }
+
+    preferred.map_or(RedirectFormat::Redirect, |(format, _)| format)
+}
+
+/// Only accepts directives that make sense for a redirect, normalized to lowercase and
+/// separated by a comma. Browsers never ask again for a permanent redirect anyway, so
+/// `no-store` on a permanent link only hides that it is cached forever.
+fn parse_cache_control(cache_control: Option<&str>, permanent: bool) -> Result<Option<String>, LinkInputError> {
+    let Some(cache_control) = cache_control else {
+        return Ok(None);
+    };
+
+    let directives = cache_control
+        .split(',')
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    let is_known = |directive: &str| match directive.split_once('=') {
+        Some((name, seconds)) => {
+            CACHE_CONTROL_DIRECTIVES_WITH_SECONDS.contains(&name)
+                && !seconds.is_empty()
+                && seconds.chars().all(|c| c.is_ascii_digit())
+        }
+        None => CACHE_CONTROL_DIRECTIVES.contains(&directive),
+    };
+
+    if !directives.iter().all(|directive| is_known(directive)) {
+        return Err(LinkInputError::InvalidCacheControl);
+    }
+
+    if permanent && directives.iter().any(|directive| directive == "no-store") {
+        return Err(LinkInputError::NoStorePermanentLink);
+    }
+
+    Ok(Some(directives.join(", ")))
+}
+
+fn resolve_auto_expiry(new_link: &mut CreateLinkRequest) -> Result<(), AppError> {
+    let Some(expires_in_secs) = new_link.auto_expires_in_secs else {
+        return Ok(());
+    };
+
+    if new_link.expires_at.is_some() {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "conflicting_expiry",
+            "only one of expires at and auto expires in secs can be given",
+        )));
+    }
+
+    if !(MIN_AUTO_EXPIRES_IN_SECS..=MAX_AUTO_EXPIRES_IN_SECS).contains(&expires_in_secs) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "invalid_auto_expiry",
+            format!(
+                "auto expires in secs must be between {} and {}",
+                MIN_AUTO_EXPIRES_IN_SECS, MAX_AUTO_EXPIRES_IN_SECS
+            ),
+        )));
+    }
+
+    new_link.expires_at = Some(Utc::now() + chrono::Duration::seconds(expires_in_secs as i64));
+
+    Ok(())
+}
+
+fn alias_not_updatable() -> AppError {
+    AppError::Conflict(ErrorBody::new(
+        "link_is_alias",
+        "aliases follow the target url of their link and cannot be updated",
+    ))
+}
+
+/// Trims, sorts and deduplicates the tags, so that they can be stored and returned as is
+fn parse_tags(tags: Option<&[String]>) -> Result<Option<Vec<String>>, LinkInputError> {
+    let Some(tags) = tags else {
+        return Ok(None);
+    };
+
+    let mut tags: Vec<String> = tags.iter().map(|tag| tag.trim().to_owned()).collect();
+
+    if tags.iter().any(|tag| tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH) {
+        return Err(LinkInputError::InvalidTag);
+    }
+
+    tags.sort();
+    tags.dedup();
+
+    Ok(Some(tags))
+}
+
+fn parse_utm_params(utm_params: Option<&HashMap<String, String>>) -> Result<Option<UtmParams>, LinkInputError> {
+    let Some(utm_params) = utm_params else {
+        return Ok(None);
+    };
+
+    if utm_params
+        .keys()
+        .any(|name| name.trim().is_empty() || name.chars().count() > MAX_UTM_PARAM_NAME_LENGTH)
+    {
+        return Err(LinkInputError::InvalidUtmParam);
+    }
+
+    Ok(Some(sqlx::types::Json(utm_params.clone())))
+}
+
+/// Robots tags consist of directives for many different crawlers, like `googlebot: noindex`,
+/// so only what is needed to send it as a header is checked.
+pub fn is_valid_robots_tag(robots_tag: &str) -> bool {
+    !robots_tag.trim().is_empty()
+        && robots_tag.len() <= MAX_ROBOTS_TAG_LENGTH
+        && robots_tag.bytes().all(|byte| byte == b' ' || byte.is_ascii_graphic())
+}
+
+fn parse_robots_tag(robots_tag: Option<&str>) -> Result<Option<String>, LinkInputError> {
+    match robots_tag.map(str::trim) {
+        Some(robots_tag) if !is_valid_robots_tag(robots_tag) => Err(LinkInputError::InvalidRobotsTag),
+        robots_tag => Ok(robots_tag.map(str::to_owned)),
+    }
+}
+
+/// Adds the utm params of the link to its target url. Params the target url already
+/// has are left as they are, and the others are added sorted to keep the url stable.
+fn redirect_target_url(link: &Link) -> String {
+    let Some(utm_params) = &link.utm_params else {
+        return link.target_url.clone();
+    };
+
+    let Ok(mut url) = Url::parse(&link.target_url) else {
+        return link.target_url.clone();
+    };
+
+    let mut missing_params: Vec<(&String, &String)> = utm_params
+        .iter()
+        .filter(|(name, _)| !url.query_pairs().any(|(existing_name, _)| existing_name == name.as_str()))
+        .collect();
+
+    if missing_params.is_empty() {
+        return link.target_url.clone();
+    }
+
+    missing_params.sort();
+    url.query_pairs_mut().extend_pairs(missing_params);
+
+    url.to_string()
+}
+
+pub(crate) fn custom_id_regex() -> &'static Regex {
+    static CUSTOM_ID_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    CUSTOM_ID_REGEX.get_or_init(|| {
+        Regex::new(r"^[a-zA-Z0-9-]{3,64}$").expect("The custom id regex should always compile")
+    })
+}
+
+fn bulk_max_links() -> usize {
+    static BULK_MAX_LINKS: OnceLock<usize> = OnceLock::new();
+
+    *BULK_MAX_LINKS.get_or_init(|| {
+        std::env::var("BULK_MAX_LINKS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_BULK_MAX_LINKS)
+    })
+}
+
+/// Generates an id of the configured length that is not reserved. Ids are only unique
+/// with high probability, inserts retry with a new id on collisions.
+pub fn generate_id(state: &AppState) -> String {
+    let length = state.id_length;
+
+    loop {
+        let id = nanoid!(length, &ID_ALPHABET);
+
+        if !state.link_id_blacklist.contains(&id) {
+            return id;
+        }
+    }
+}
+
+fn custom_id_reserved() -> AppError {
+    AppError::UnprocessableEntity(ErrorBody::new("custom_id_reserved", "custom id is reserved"))
+}
+
+/// Tells how often generated ids collide, which hints at whether the ids are long enough
+fn record_id_collision(link_id: &str, attempt: u32) {
+    tracing::warn!("Generated id {} already exists in attempt {}, retrying", link_id, attempt);
+    increment_counter!("link_id_collision_total");
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "Service is healthy", body = Health),
+        (status = 503, description = "Service is degraded", body = Health)
+    )
+)]
+pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
+    let ping_timeout = tokio::time::Duration::from_millis(200);
+
+    let db = match timed_query("ping", ping_timeout, sqlx::query("select 1").execute(&state.db)).await {
+        Ok(Ok(_)) => ComponentStatus::Ok,
+        Ok(Err(err)) => {
+            tracing::error!("Health check could not reach the database: {}", err);
+
+            ComponentStatus::Error
+        }
+        Err(elapsed) => {
+            tracing::error!("Health check pinging the database resulted in a timeout: {}", elapsed);
+
+            ComponentStatus::Error
+        }
+    };
+
+    let (status_code, status) = match db {
+        ComponentStatus::Ok => (StatusCode::OK, HealthStatus::Ok),
+        ComponentStatus::Error => (StatusCode::SERVICE_UNAVAILABLE, HealthStatus::Degraded),
+    };
+
+    // Load balancers read the status of a degraded service from the body
+    (
+        status_code,
+        Extension(KeepServerErrorBody),
+        Json(Health {
+            status,
+            db,
+            uptime_secs: state.started_at.elapsed().as_secs(),
+        }),
+    )
+}
+
+/// Looks the link up in redis first when it is configured, as it is shared between
+/// all replicas, and only falls back to Postgres on a miss. Aliases are resolved
+/// through their canonical link, whose target and state they redirect with.
+async fn fetch_link(state: &AppState, link_id: &str) -> Result<Link, AppError> {
+    if let Some(redis) = &state.redis {
+        if let Some(link) = redis_cache::get_link(redis, link_id).await {
+            increment_counter!("redis_cache_hits_total");
+
+            return Ok(link);
+        }
+
+        increment_counter!("redis_cache_misses_total");
+    }
+
+    let select_timeout = state.config.db_query_timeout;
+
+    let link = timed_query(
+        "select_link",
+        select_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            select links.id, coalesce(canonical.target_url, links.target_url) as "target_url!",
+                least(links.expires_at, canonical.expires_at) as expires_at, links.is_permanent,
+                least(links.max_clicks, canonical.max_clicks) as max_clicks, links.webhook_url, links.title,
+                links.description, links.cache_control, links.robots_tag,
+                case when canonical.id is null then links.utm_params else canonical.utm_params end as "utm_params: UtmParams",
+                links.signed or coalesce(canonical.signed, false) as "signed!",
+                links.active and coalesce(canonical.active, true) as "active!",
+                links.created_at, links.updated_at, links.deleted_at, link_tag_names(links.id) as "tags!"
+            from links
+            left join links canonical on canonical.id = links.canonical_id
+            where links.id = $1 and links.deleted_at is null and canonical.deleted_at is null
+            "#,
+            link_id
+        )
+            .fetch_optional(&state.db),
+    )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?
+        .ok_or_else(|| AppError::NotFound(ErrorBody::new("link_not_found", "Not found")))?;
+
+    if let Some(redis) = &state.redis {
+        redis_cache::set_link(redis, &link, state.link_cache_ttl.get()).await;
+    }
+
+    Ok(link)
+}
+
+async fn cached_link(state: &AppState, link_id: &str) -> Result<Link, AppError> {
+    if let Some(link) = state.link_cache.get(link_id).await {
+        increment_counter!("cache_hits_total");
+
+        return Ok(link);
+    }
+
+    increment_counter!("cache_misses_total");
+
+    let link = fetch_link(state, link_id).await?;
+
+    state.link_cache.insert(link_id.to_owned(), link.clone()).await;
+
+    Ok(link)
+}
+
+/// Deployments may send no robots tag at all, links without one of their own then send none either
+fn redirect_robots_tag<'a>(state: &'a AppState, link: &'a Link) -> Option<&'a str> {
+    link.robots_tag.as_deref().or(state.redirect_robots_tag.as_deref())
+}
+
+fn redirect_cache_control(link: &Link) -> String {
+    if link.signed {
+        SIGNED_CACHE_CONTROL_HEADER_VALUE.to_owned()
+    } else {
+        link.cache_control
+            .clone()
+            .unwrap_or_else(|| DEFAULT_CACHE_CONTROL_HEADER_VALUE.to_owned())
+    }
+}
+
+async fn invalidate_cached_id(state: &AppState, link_id: &str) {
+    state.link_cache.invalidate(link_id).await;
+
+    if let Some(redis) = &state.redis {
+        redis_cache::invalidate_link(redis, link_id).await;
+    }
+}
+
+/// Aliases are cached with the state of their canonical link, so their cached
+/// copies are outdated as well whenever the link changes.
+pub async fn invalidate_cached_link(state: &AppState, link_id: &str) {
+    invalidate_cached_id(state, link_id).await;
+
+    let select_alias_ids_timeout = state.config.db_query_timeout;
+
+    let alias_ids = timed_query(
+        "select_alias_ids",
+        select_alias_ids_timeout,
+        sqlx::query_scalar!("select id from links where canonical_id = $1", link_id)
+            .fetch_all(&state.db),
+    )
+    .await;
+
+    match alias_ids {
+        Ok(Ok(alias_ids)) => {
+            for alias_id in &alias_ids {
+                invalidate_cached_id(state, alias_id).await;
+            }
+        }
+        Ok(Err(err)) => tracing::error!("Selecting the aliases of link with id {} failed with the following error: {}", link_id, err),
+        Err(elapsed) => tracing::error!("Selecting the aliases of link with id {} resulted in a timeout: {}", link_id, elapsed),
+    }
+}
+
+/// Points all aliases of the link to its new target url within the transaction that
+/// updates the link, so that they list the same target as the link itself. Redirects
+/// resolve aliases through the link and do not depend on it.
+async fn update_aliases(
+    state: &AppState,
+    connection: &mut PgConnection,
+    link_id: &str,
+    target_url: &str,
+    utm_params: Option<&UtmParams>,
+) -> Result<(), AppError> {
+    let update_aliases_timeout = state.config.db_query_timeout;
+
+    let alias_ids = timed_query(
+        "update_aliases",
+        update_aliases_timeout,
+        sqlx::query_scalar!(
+            r#"
+            update links set target_url = $1, utm_params = $3,
+                preview_fetched_at = case when target_url = $1 then preview_fetched_at end
+            where canonical_id = $2 and (target_url <> $1 or utm_params is distinct from $3)
+            returning id
+            "#,
+            target_url,
+            link_id,
+            utm_params as Option<&UtmParams>
+        )
+        .fetch_all(connection),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    if !alias_ids.is_empty() {
+        tracing::debug!("Updated {} aliases of link with id {}", alias_ids.len(), link_id);
+    }
+
+    Ok(())
+}
+
+/// Everything that keeps a link from being redirected to, besides its click limit, which
+/// needs its clicks. Redirects and HEAD requests answer the same way for the same link.
+fn check_redirectable(state: &AppState, link: &Link, signature: &SignatureQuery) -> Result<(), AppError> {
+    // Deactivated links look exactly like unknown ones, so that nobody can tell they exist
+    if !link.active {
+        tracing::debug!("Link with id {} is deactivated, refusing to redirect", link.id);
+
+        return Err(AppError::NotFound(ErrorBody::new("link_not_found", "Not found")));
+    }
+
+    // Signatures are verified whenever one is present, but only required for signed links
+    if link.signed || signature.sig.is_some() {
+        let exp = signature.exp.as_deref().and_then(|exp| exp.parse().ok());
+
+        let is_valid = match (&state.signing_secret, &signature.sig, exp) {
+            (Some(secret), Some(sig), Some(exp)) => signing::verify(secret, &link.id, exp, sig),
+            _ => false,
+        };
+
+        if !is_valid {
+            tracing::debug!("Invalid or expired signature for link with id {}, refusing to redirect", link.id);
+            increment_counter!("invalid_signature_redirects_total");
+
+            return Err(AppError::Forbidden(ErrorBody::new("invalid_signature", "Invalid or expired signature")));
+        }
+    }
+
+    if link.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        tracing::debug!("Link with id {} expired, refusing to redirect", link.id);
+        increment_counter!("link_expired_redirects_total");
+
+        return Err(AppError::Gone(ErrorBody::new("link_expired", "Link expired")));
+    }
+
+    if Url::parse(&link.target_url).is_ok_and(|url| state.blocklist.is_blocked(&url)) {
+        tracing::warn!(
+            "Link with id {} targets blocked url {}, refusing to redirect",
+            link.id,
+            link.target_url
+        );
+
+        return Err(AppError::UnavailableForLegalReasons(ErrorBody::new(
+            "link_blocked",
+            "Unavailable for legal reasons",
+        )));
+    }
+
+    Ok(())
+}
+
+/// A link and its aliases share their clicks, as they lead to the same place
+async fn count_clicks(connection: &mut PgConnection, timeout: Duration, link_id: &str) -> Result<i64, AppError> {
+    timed_query(
+        "count_clicks",
+        timeout,
+        sqlx::query_scalar!(
+            r#"
+            select count(*) as "count!" from link_statistics
+            where link_id in (
+                select id from links
+                where coalesce(canonical_id, id) = (select coalesce(canonical_id, id) from links where id = $1)
+            )
+            "#,
+            link_id
+        )
+        .fetch_one(connection),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)
+}
+
+/// Records a click of a link with a click limit and returns the number of its clicks, this
+/// one included. Clicks of the same link take turns on the row of the canonical link, so
+/// that each count covers every click before it and no two clicks get the last one. A click
+/// that cannot be recorded fails, as it could not be counted either.
+async fn record_limited_click(
+    state: &AppState,
+    link_id: &str,
+    insert_click: sqlx::query::Query<'_, Postgres, PgArguments>,
+) -> Result<i64, AppError> {
+    let record_click_timeout = state.config.db_query_timeout;
+
+    let mut transaction = state.db.begin().await.map_err(internal_error)?;
+
+    timed_query(
+        "lock_link_clicks",
+        record_click_timeout,
+        sqlx::query!(
+            "select id from links where id = (select coalesce(canonical_id, id) from links where id = $1) for no key update",
+            link_id
+        )
+        .fetch_optional(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let clicks = count_clicks(&mut transaction, record_click_timeout, link_id).await?;
+
+    timed_query("insert_statistics", record_click_timeout, insert_click.execute(&mut *transaction))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    Ok(clicks + 1)
+}
+
+fn click_limit_reached(link_id: &str) -> AppError {
+    tracing::debug!("Link with id {} reached its click limit, refusing to redirect", link_id);
+    increment_counter!("link_click_limit_reached_redirects_total");
+
+    AppError::Gone(ErrorBody::new("click_limit_reached", "Link reached its click limit"))
+}
+
+#[utoipa::path(
+    head,
+    path = "/{id}",
+    params(("id" = String, Path, description = "Id of the short link"), SignatureQuery),
+    responses(
+        (status = 200, description = "Link exists, with the cache headers of its redirect"),
+        (status = 403, description = "Signature invalid, expired, or missing for a signed link"),
+        (status = 404, description = "Link not found"),
+        (status = 410, description = "Link expired or reached its click limit"),
+        (status = 451, description = "Target url of the link is blocked")
+    )
+)]
+#[tracing::instrument(skip_all, fields(link.id = %requested_link, http.method = "HEAD"))]
+pub async fn link_exists(
+    State(state): State<AppState>,
+    Path(requested_link): Path<String>,
+    Query(signature): Query<SignatureQuery>,
+) -> Result<Response, AppError> {
+    let link = cached_link(&state, &requested_link).await?;
+
+    check_redirectable(&state, &link, &signature)?;
+
+    // HEAD requests are no clicks, so the limit is reached once the redirects used it up
+    if let Some(max_clicks) = link.max_clicks {
+        let mut connection = state.db.acquire().await.map_err(internal_error)?;
+
+        if count_clicks(&mut connection, state.config.db_query_timeout, &requested_link).await? >= max_clicks {
+            return Err(click_limit_reached(&requested_link));
+        }
+    }
+
+    tracing::debug!("Link with id {} exists", requested_link);
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header("Cache-Control", redirect_cache_control(&link))
+        .header(VARY, ACCEPT.as_str())
+        .header(ETAG, entity_tag(&link))
+        .header(LAST_MODIFIED, http_date(link.updated_at));
+
+    if let Some(robots_tag) = redirect_robots_tag(&state, &link) {
+        response = response.header(X_ROBOTS_TAG, robots_tag);
+    }
+
+    Ok(response
+        .body(Body::empty())
+        .expect("This response should always be constructable"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    params(("id" = String, Path, description = "Id of the short link"), SignatureQuery),
+    responses(
+        (status = 200, description = "Target url of the link for clients preferring text/plain or application/json", content(
+            ("text/plain" = String),
+            ("application/json" = RedirectTarget)
+        )),
+        (status = 301, description = "Permanent redirect to the target url of the link"),
+        (status = 304, description = "Link not modified since the client last followed it"),
+        (status = 307, description = "Temporary redirect to the target url of the link"),
+        (status = 403, description = "Signature invalid, expired, or missing for a signed link"),
+        (status = 404, description = "Link not found"),
+        (status = 410, description = "Link expired or reached its click limit"),
+        (status = 451, description = "Target url of the link is blocked")
+    )
+)]
+#[tracing::instrument(skip_all, fields(link.id = %requested_link, link.target_url = tracing::field::Empty, http.method = "GET"))]
+pub async fn redirect(
+    State(state): State<AppState>,
+    Path(requested_link): Path<String>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Query(signature): Query<SignatureQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let link = cached_link(&state, &requested_link).await?;
+
+    tracing::Span::current().record("link.target_url", link.target_url.as_str());
+
+    check_redirectable(&state, &link, &signature)?;
+
+    let entity_tag = entity_tag(&link);
+    let last_modified = http_date(link.updated_at);
+
+    let cache_control = redirect_cache_control(&link);
+    let target_url = redirect_target_url(&link);
+
+    // Nobody navigated anywhere, so there is no click to record
+    if is_not_modified(&headers, &entity_tag, link.updated_at) {
+        tracing::debug!("Link with id {} not modified, skipping the redirect", requested_link);
+
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(VARY, ACCEPT.as_str())
+            .header(ETAG, entity_tag)
+            .header(LAST_MODIFIED, last_modified)
+            .header("Cache-Control", cache_control)
+            .body(Body::empty())
+            .expect("This response should always be constructable"));
+    }
+
+    tracing::debug!(
+        "Redirecting link id {} to {}",
+        requested_link,
+        link.target_url
+    );
+
+    let referer_header = headers
+        .get("referer")
+        .map(|value| value.to_str().unwrap_or_default().to_string());
+
+    let user_agent_header = headers
+        .get("user-agent")
+        .map(|value| value.to_str().unwrap_or_default().to_string());
+
+    // The raw header is stored as is, but cut off to fit into the column
+    let locale_header = headers
+        .get("accept-language")
+        .map(|value| {
+            value
+                .to_str()
+                .unwrap_or_default()
+                .chars()
+                .take(MAX_LOCALE_LENGTH)
+                .collect::<String>()
+        });
+
+    let ip_hash = hash_ip(client_ip(&headers, peer));
+    let (browser, os) = parse_user_agent(user_agent_header.as_deref());
+
+    let insert_click = sqlx::query(
+        r#"
+            insert into link_statistics(link_id, referer, user_agent, locale, ip_hash, browser, os)
+            values($1, $2, $3, $4, $5, $6, $7)
+            "#,
+    )
+        .bind(&requested_link)
+        .bind(&referer_header)
+        .bind(&user_agent_header)
+        .bind(&locale_header)
+        .bind(&ip_hash)
+        .bind(&browser)
+        .bind(&os);
+
+    let (saved_statistic, clicks) = match link.max_clicks {
+        Some(_) => (true, Some(record_limited_click(&state, &requested_link, insert_click).await?)),
+        None => (record_click(&state, insert_click).await, None),
+    };
+
+    if saved_statistic {
+        tracing::debug!(
+            "Persisted new link click for link with id {}, referer {}, user_agent {}, and locale {}",
+            requested_link,
+            referer_header.as_deref().unwrap_or_default(),
+            user_agent_header.as_deref().unwrap_or_default(),
+            locale_header.as_deref().unwrap_or_default()
+        );
+
+        if let Some(webhook_url) = &link.webhook_url {
+            notify_click(
+                state.http_client.clone(),
+                webhook_url.clone(),
+                ClickEvent {
+                    link_id: requested_link.clone(),
+                    target_url: link.target_url.clone(),
+                    referer: referer_header,
+                    user_agent: user_agent_header,
+                    clicked_at: Utc::now(),
+                },
+            );
+        }
+    }
+
+    // The click that exceeds the limit has been recorded as well, so that
+    // the statistics also show attempts to follow an exhausted link
+    if let (Some(max_clicks), Some(clicks)) = (link.max_clicks, clicks) {
+        if clicks > max_clicks {
+            return Err(click_limit_reached(&requested_link));
+        }
+    }
+
+    // Labeling by link id produces one time series per link, which can get expensive
+    // for Prometheus once there are many links. Global dashboards should therefore
+    // rely on the unlabeled counter and only per-link views should use the labeled one.
+    let labels = [("link_id", requested_link.clone())];
+    increment_counter!("redirects_total", &labels);
+    increment_counter!("redirects_total_unlabeled");
+
+    // Caches must not answer a browser with the body meant for a script or vice versa
+    let mut response = Response::builder()
+        .header("Cache-Control", cache_control)
+        .header(VARY, ACCEPT.as_str())
+        .header(ETAG, entity_tag)
+        .header(LAST_MODIFIED, last_modified);
+
+    // Crawlers follow short links like any other, which would list them next to their targets
+    if let Some(robots_tag) = redirect_robots_tag(&state, &link) {
+        response = response.header(X_ROBOTS_TAG, robots_tag);
+    }
+
+    let response = match redirect_format(&headers) {
+        RedirectFormat::PlainText => response
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Body::from(target_url)),
+        RedirectFormat::Json => response
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::to_vec(&RedirectTarget { target_url })
+                    .expect("The redirect target should always be serializable"),
+            )),
+        RedirectFormat::Redirect => {
+            let status = if link.is_permanent && !link.signed {
+                StatusCode::MOVED_PERMANENTLY
+            } else {
+                StatusCode::TEMPORARY_REDIRECT
+            };
+
+            response
+                .status(status)
+                .header("Location", target_url)
+                .body(Body::empty())
+        }
+    };
+
+    Ok(response.expect("This response should always be constructable"))
+}
+
+/// Records a click of a link without a click limit. Failures only cost the statistics
+/// the click, so the visitor is redirected either way.
+async fn record_click(state: &AppState, insert_click: sqlx::query::Query<'_, Postgres, PgArguments>) -> bool {
+    let insert_statistics_timeout = state.config.db_query_timeout;
+
+    match timed_query("insert_statistics", insert_statistics_timeout, insert_click.execute(&state.db)).await {
+        Err(elapsed) => tracing::error!("Saving new link click resulted in a timeout: {}", elapsed),
+        Ok(Err(err)) => tracing::error!(
+            "Saving a new link click failed with the following error: {}",
+            err
+        ),
+        Ok(Ok(_)) => return true,
+    }
+
+    false
+}
+
+fn created_short_link(state: &AppState, link: Link) -> ShortLink {
+    let mut short_link = ShortLink::new(link, &state.base_url);
+
+    if let (true, Some(secret)) = (short_link.link.signed, &state.signing_secret) {
+        let expires_at = short_link.link.expires_at.map_or_else(
+            || Utc::now().timestamp() + state.signed_url_ttl.as_secs() as i64,
+            |expires_at| expires_at.timestamp(),
+        );
+
+        short_link.signed_url = Some(signing::signed_url(
+            secret,
+            &short_link.short_url,
+            &short_link.link.id,
+            expires_at,
+        ));
+    }
+
+    short_link
+}
+
+/// Remembers the link under the idempotency key of the request, so that retries get it
+/// again, and commits it together with the link
+async fn created_link(
+    state: &AppState,
+    mut transaction: Transaction<'static, Postgres>,
+    link: Link,
+    idempotency_key: Option<Uuid>,
+    workspace_id: Option<Uuid>,
+) -> Result<(StatusCode, ShortLink), AppError> {
+    if let Some(key) = idempotency_key {
+        idempotency::remember_link(&mut transaction, state.config.db_query_timeout, key, workspace_id, &link.id)
+            .await?;
+    }
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, created_short_link(state, link)))
+}
+
+/// Answers a request whose idempotency key was claimed before with the link created for it
+async fn replayed_link(
+    state: &AppState,
+    key: Uuid,
+    workspace_id: Option<Uuid>,
+) -> Result<(StatusCode, ShortLink), AppError> {
+    let existing_link = idempotency::find_link(&state.db, state.config.db_query_timeout, key, workspace_id)
+        .await?;
+
+    // The link of the key was deleted for good after it was created
+    let Some(link) = existing_link else {
+        return Err(AppError::Conflict(ErrorBody::new(
+            "idempotency_key_in_use",
+            "idempotency key was already used for a link that no longer exists",
+        )));
+    };
+
+    tracing::Span::current().record("link.id", link.id.as_str());
+    tracing::debug!("Returned link with id {} created before for idempotency key {}", link.id, key);
+
+    Ok((StatusCode::OK, created_short_link(state, link)))
+}
+
+/// Inserts the link within a savepoint of the transaction that creates it, so that a
+/// failed insert only rolls back itself and retrying with another id keeps the claimed
+/// idempotency key.
+async fn insert_link(
+    state: &AppState,
+    connection: &mut PgConnection,
+    link_id: &str,
+    tags: &[String],
+    utm_params: Option<&UtmParams>,
+    creator: &LinkCreator,
+    new_link: &CreateLinkRequest,
+) -> Result<Result<Link, Error>, AppError> {
+    let mut savepoint = connection.begin().await.map_err(internal_error)?;
+
+    // Existing tags are updated to themselves, because on conflict do nothing would not return their ids
+    let link = timed_query(
+        "insert_link",
+        state.config.db_query_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            with inserted_link as (
+                insert into links(
+                    id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, signed,
+                    creator_user_agent, created_by_key_id, cache_control, workspace_id, utm_params, robots_tag
+                )
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $11, $12, $13, $14, $15, $16)
+                returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at
+            ), upserted_tags as (
+                insert into tags(name) select unnest($10::text[])
+                on conflict (name) do update set name = excluded.name
+                returning id
+            ), inserted_link_tags as (
+                insert into link_tags(link_id, tag_id)
+                select inserted_link.id, upserted_tags.id from inserted_link, upserted_tags
+            )
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, $10::text[] as "tags!" from inserted_link
+            "#,
+            link_id,
+            new_link.target_url,
+            new_link.expires_at,
+            new_link.permanent.unwrap_or(false),
+            new_link.max_clicks,
+            new_link.webhook_url,
+            new_link.title,
+            new_link.description,
+            new_link.signed.unwrap_or(false),
+            tags,
+            creator.user_agent,
+            creator.api_key_id,
+            new_link.cache_control,
+            creator.workspace_id,
+            utm_params as Option<&UtmParams>,
+            new_link.robots_tag
+        )
+        .fetch_one(&mut *savepoint)
+    )
+    .await
+    .map_err(internal_error)?;
+
+    // A failed insert is rolled back by dropping the savepoint
+    Ok(match link {
+        Ok(link) => savepoint.commit().await.map(|_| link),
+        Err(err) => Err(err),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/info",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Metadata of the link", body = LinkInfo),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_link_info(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<LinkInfo, AppError> {
+    let select_timeout = config.db_query_timeout;
+
+    let link = timed_query(
+        "select_link_info",
+        select_timeout,
+        sqlx::query!(
+            r#"
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, link_tag_names(id) as "tags!",
+                creator_user_agent, created_by_key_id, canonical_id,
+                array(
+                    select aliases.id from links aliases
+                    where aliases.canonical_id = links.id and aliases.deleted_at is null
+                    order by aliases.id
+                ) as "aliases!"
+            from links where id = $1 and deleted_at is null and workspace_id is not distinct from $2
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_optional(&pool),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        AppError::NotFound(ErrorBody::new("link_not_found", "Not found"))
+    })?;
+
+    tracing::debug!("Info for link with id {} requested", link_id);
+
+    Ok(LinkInfo {
+        link: Link {
+            id: link.id,
+            target_url: link.target_url,
+            expires_at: link.expires_at,
+            is_permanent: link.is_permanent,
+            max_clicks: link.max_clicks,
+            webhook_url: link.webhook_url,
+            title: link.title,
+            description: link.description,
+            cache_control: link.cache_control,
+            robots_tag: link.robots_tag,
+            utm_params: link.utm_params,
+            signed: link.signed,
+            active: link.active,
+            tags: link.tags,
+            created_at: link.created_at,
+            updated_at: link.updated_at,
+            deleted_at: link.deleted_at,
+        },
+        creator_user_agent: link.creator_user_agent,
+        created_by_key_id: link.created_by_key_id,
+        canonical_id: link.canonical_id,
+        aliases: link.aliases,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/qr",
+    params(("id" = String, Path, description = "Id of the short link"), QrCodeQuery),
+    responses(
+        (status = 200, description = "PNG image encoding the short url", content_type = "image/png"),
+        (status = 404, description = "Link not found"),
+        (status = 422, description = "Size out of range")
+    )
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_qr_code(
+    State(state): State<AppState>,
+    Path(link_id): Path<String>,
+    Query(query): Query<QrCodeQuery>,
+) -> Result<Response, AppError> {
+    let size = query.size.unwrap_or(DEFAULT_QR_CODE_SIZE);
+
+    if !(MIN_QR_CODE_SIZE..=MAX_QR_CODE_SIZE).contains(&size) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "invalid_size",
+            format!(
+                "size must be between {} and {}",
+                MIN_QR_CODE_SIZE, MAX_QR_CODE_SIZE
+            ),
+        )));
+    }
+
+    let select_timeout = state.config.db_query_timeout;
+
+    let link_id = timed_query(
+        "select_link_id",
+        select_timeout,
+        sqlx::query_scalar!("select id from links where id = $1 and deleted_at is null", &link_id)
+            .fetch_optional(&state.db),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?
+    .ok_or_else(|| AppError::NotFound(ErrorBody::new("link_not_found", "Not found")))?;
+
+    let short_url = format!("{}/{}", state.base_url, link_id);
+
+    let qr_code_image = QrCode::new(short_url.as_bytes())
+        .map_err(internal_error)?
+        .render::<Luma<u8>>()
+        .min_dimensions(size, size)
+        .max_dimensions(size, size)
+        .build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(qr_code_image)
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .map_err(internal_error)?;
+
+    tracing::debug!("Generated QR code for link with id {}", link_id);
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "image/png")
+        .header("Cache-Control", QR_CODE_CACHE_CONTROL_HEADER_VALUE)
+        .body(Body::from(png))
+        .expect("This response should always be constructable"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/create",
+    request_body(
+        content = CreateLinkRequest,
+        description = "Accepted as json or as url encoded form"
+    ),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Uuid under which retries within 24 hours return the same link")
+    ),
+    responses(
+        (status = 200, description = "Link created before with the same idempotency key", body = ShortLink),
+        (status = 201, description = "Created link", body = ShortLink),
+        (status = 409, description = "Custom id already taken, or idempotency key used for a link deleted since"),
+        (status = 415, description = "Body is neither json nor a url encoded form"),
+        (status = 422, description = "Custom id malformed or reserved, target url malformed or not allowed, webhook url invalid, title too long, tag, cache control, or utm param invalid, expiry invalid, idempotency key invalid, or signing not configured"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = tracing::field::Empty, link.target_url = tracing::field::Empty, http.method = "POST"))]
+pub async fn create_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    headers: HeaderMap,
+    JsonOrForm(mut new_link): JsonOrForm<CreateLinkRequest>,
+) -> Result<(StatusCode, ShortLink), AppError> {
+    new_link.target_url = parse_target_url(&new_link.target_url, &state.blocklist)?;
+    tracing::Span::current().record("link.target_url", new_link.target_url.as_str());
+    new_link.webhook_url =
+        parse_webhook_url(new_link.webhook_url.as_deref())?;
+    new_link.cache_control =
+        parse_cache_control(new_link.cache_control.as_deref(), new_link.permanent.unwrap_or(false))?;
+    new_link.robots_tag = parse_robots_tag(new_link.robots_tag.as_deref())?;
+    let tags = parse_tags(new_link.tags.as_deref())?
+        .unwrap_or_default();
+    let utm_params = parse_utm_params(new_link.utm_params.as_ref())?;
+    resolve_auto_expiry(&mut new_link)?;
+
+    if new_link.signed == Some(true) && state.signing_secret.is_none() {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "signing_not_configured",
+            "signed links require SIGNING_SECRET to be set",
+        )));
+    }
+
+    let idempotency_key = idempotency::idempotency_key(&headers)?;
+    let creator = LinkCreator::new(&headers, api_key);
+
+    if let Some(custom_id) = &new_link.custom_id {
+        if !custom_id_regex().is_match(custom_id) {
+            return Err(AppError::UnprocessableEntity(ErrorBody::new("custom_id_malformed", "custom id malformed")));
+        }
+
+        if state.link_id_blacklist.contains(custom_id) {
+            return Err(custom_id_reserved());
+        }
+    }
+
+    let mut transaction = audit::begin(&state.db, creator.api_key_id).await?;
+
+    // A retry racing the original request waits here until the original commits its link
+    if let Some(key) = idempotency_key {
+        let claimed = idempotency::claim_link(&mut transaction, state.config.db_query_timeout, key, api_key.workspace_id)
+            .await?;
+
+        if !claimed {
+            drop(transaction);
+
+            return replayed_link(&state, key, api_key.workspace_id).await;
+        }
+    }
+
+    if let Some(custom_id) = &new_link.custom_id {
+        return match insert_link(
+            &state,
+            &mut transaction,
+            custom_id,
+            &tags,
+            utm_params.as_ref(),
+            &creator,
+            &new_link,
+        )
+            .await?
+        {
+            Ok(link) => {
+                tracing::Span::current().record("link.id", custom_id.as_str());
+                tracing::debug!("Created new link with custom id {} targeting {}", custom_id, new_link.target_url);
+
+                created_link(&state, transaction, link, idempotency_key, api_key.workspace_id).await
+            }
+            Err(Error::Database(db_err)) if db_err.kind() == ErrorKind::UniqueViolation => {
+                Err(AppError::Conflict(ErrorBody::new("custom_id_taken", "custom id already taken")))
+            }
+            Err(err) => Err(internal_error(err)),
+        };
+    }
+
+    for attempt in 1..=3 {
+        let new_link_id = generate_id(&state);
+
+        let new_link = insert_link(
+            &state,
+            &mut transaction,
+            &new_link_id,
+            &tags,
+            utm_params.as_ref(),
+            &creator,
+            &new_link,
+        )
+            .await?;
+
+        match new_link {
+            Ok(link) => {
+                tracing::Span::current().record("link.id", new_link_id.as_str());
+                tracing::debug!("Created new link with id {} targeting {}", new_link_id, link.target_url);
+
+                return created_link(&state, transaction, link, idempotency_key, api_key.workspace_id).await
+            }
+            Err(err) => match err {
+                Error::Database(db_err) if db_err.kind() == ErrorKind::UniqueViolation => {
+                    record_id_collision(&new_link_id, attempt);
+                }
+                _ => return Err(internal_error(err))
+            }
+        }
+    }
+
+    tracing::error!("Could not persist new short link. Exhausted all retries of generating a unique id");
+    increment_counter!("saving_link_impossible_no_unique_id");
+
+    Err(AppError::Internal(ErrorBody::new("internal_error", "Internal server error")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/bulk",
+    request_body = Vec<LinkTarget>,
+    responses(
+        (status = 200, description = "Created links in the order of the request", body = Vec<ShortLink>),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Too many links or invalid link inputs", body = Vec<BulkLinkError>)
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(http.method = "POST"))]
+pub async fn create_links_in_bulk(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    headers: HeaderMap,
+    Json(new_links): Json<Vec<LinkTarget>>,
+) -> Result<Response, AppError> {
+    let max_links = bulk_max_links();
+
+    if new_links.len() > max_links {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new(
+            "too_many_links",
+            format!("at most {} links can be created at once", max_links),
+        )));
+    }
+
+    let mut urls = Vec::with_capacity(new_links.len());
+    let mut webhook_urls = Vec::with_capacity(new_links.len());
+    let mut tags = Vec::with_capacity(new_links.len());
+    let expires_ats: Vec<Option<DateTime<Utc>>> =
+        new_links.iter().map(|new_link| new_link.expires_at).collect();
+    let permanents: Vec<bool> = new_links
+        .iter()
+        .map(|new_link| new_link.permanent.unwrap_or(false))
+        .collect();
+    let max_clicks: Vec<Option<i64>> =
+        new_links.iter().map(|new_link| new_link.max_clicks).collect();
+    let titles: Vec<Option<String>> = new_links.iter().map(|new_link| new_link.title.clone()).collect();
+    let descriptions: Vec<Option<String>> =
+        new_links.iter().map(|new_link| new_link.description.clone()).collect();
+    let mut cache_controls = Vec::with_capacity(new_links.len());
+    let mut utm_params = Vec::with_capacity(new_links.len());
+    let mut robots_tags = Vec::with_capacity(new_links.len());
+    let mut errors = Vec::new();
+
+    for (index, new_link) in new_links.iter().enumerate() {
+        // Each link is validated like the body of POST /create, the list itself has no constraints
+        if let Err(validation_errors) = new_link.validate() {
+            errors.push(BulkLinkError {
+                index,
+                error: body_validation_error(validation_errors).into_body().message,
+            });
+
+            continue;
+        }
+
+        let parsed = parse_target_url(&new_link.target_url, &state.blocklist)
+            .and_then(|url| {
+                parse_webhook_url(new_link.webhook_url.as_deref()).map(|webhook_url| (url, webhook_url))
+            })
+            .and_then(|(url, webhook_url)| {
+                parse_tags(new_link.tags.as_deref()).map(|link_tags| (url, webhook_url, link_tags))
+            })
+            .and_then(|(url, webhook_url, link_tags)| {
+                parse_cache_control(new_link.cache_control.as_deref(), new_link.permanent.unwrap_or(false))
+                    .map(|cache_control| (url, webhook_url, link_tags, cache_control))
+            })
+            .and_then(|(url, webhook_url, link_tags, cache_control)| {
+                parse_utm_params(new_link.utm_params.as_ref())
+                    .map(|link_utm_params| (url, webhook_url, link_tags, cache_control, link_utm_params))
+            })
+            .and_then(|(url, webhook_url, link_tags, cache_control, link_utm_params)| {
+                parse_robots_tag(new_link.robots_tag.as_deref())
+                    .map(|robots_tag| (url, webhook_url, link_tags, cache_control, link_utm_params, robots_tag))
+            });
+
+        match parsed {
+            Ok((url, webhook_url, link_tags, cache_control, link_utm_params, robots_tag)) => {
+                urls.push(url);
+                webhook_urls.push(webhook_url);
+                tags.push(link_tags.unwrap_or_default());
+                cache_controls.push(cache_control);
+                utm_params.push(link_utm_params);
+                robots_tags.push(robots_tag);
+            }
+            Err(err) => errors.push(BulkLinkError {
+                index,
+                error: err.message().into(),
+            }),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(errors)).into_response());
+    }
+
+    let insert_links_timeout = state.config.db_query_timeout;
+    let creator = LinkCreator::new(&headers, api_key);
+
+    for attempt in 1..=3 {
+        let new_link_ids: Vec<String> = urls.iter().map(|_| generate_id(&state)).collect();
+
+        // One pair of link id and tag name per tag, as postgres has no arrays of arrays with different lengths
+        let (tagged_link_ids, tag_names): (Vec<String>, Vec<String>) = new_link_ids
+            .iter()
+            .zip(&tags)
+            .flat_map(|(link_id, link_tags)| link_tags.iter().map(move |tag| (link_id.clone(), tag.clone())))
+            .unzip();
+
+        // Every attempt needs its own transaction, as a failed insert aborts it
+        let mut transaction = audit::begin(&state.db, creator.api_key_id).await?;
+
+        let new_links = timed_query(
+            "insert_links_bulk",
+            insert_links_timeout,
+            sqlx::query_as!(
+                Link,
+                r#"
+                with inserted_links as (
+                    insert into links(
+                        id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control,
+                        utm_params, robots_tag, workspace_id, creator_user_agent, created_by_key_id
+                    )
+                    select *, $12::uuid, $15::text, $16::uuid from unnest(
+                        $1::text[], $2::text[], $3::timestamptz[], $4::boolean[], $5::bigint[], $6::text[], $7::text[], $8::text[],
+                        $11::text[], $13::jsonb[], $14::text[]
+                    )
+                    returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at
+                ), upserted_tags as (
+                    insert into tags(name) select distinct unnest($10::text[])
+                    on conflict (name) do update set name = excluded.name
+                    returning id, name
+                ), inserted_link_tags as (
+                    insert into link_tags(link_id, tag_id)
+                    select link_tag.link_id, upserted_tags.id
+                    from unnest($9::text[], $10::text[]) as link_tag(link_id, name)
+                    join upserted_tags using (name)
+                )
+                select
+                    id as "id!",
+                    target_url as "target_url!",
+                    expires_at,
+                    is_permanent as "is_permanent!",
+                    max_clicks,
+                    webhook_url,
+                    title,
+                    description,
+                    cache_control,
+                    robots_tag,
+                    utm_params as "utm_params: UtmParams",
+                    signed as "signed!",
+                    active as "active!",
+                    created_at as "created_at!",
+                    updated_at as "updated_at!",
+                    deleted_at,
+                    coalesce(
+                        (
+                            select array_agg(link_tag.name order by link_tag.name)
+                            from unnest($9::text[], $10::text[]) as link_tag(link_id, name)
+                            where link_tag.link_id = inserted_links.id
+                        ),
+                        '{}'
+                    ) as "tags!"
+                from inserted_links
+                "#,
+                &new_link_ids,
+                &urls,
+                &expires_ats as &[Option<DateTime<Utc>>],
+                &permanents,
+                &max_clicks as &[Option<i64>],
+                &webhook_urls as &[Option<String>],
+                &titles as &[Option<String>],
+                &descriptions as &[Option<String>],
+                &tagged_link_ids,
+                &tag_names,
+                &cache_controls as &[Option<String>],
+                creator.workspace_id,
+                &utm_params as &[Option<UtmParams>],
+                &robots_tags as &[Option<String>],
+                creator.user_agent,
+                creator.api_key_id
+            )
+            .fetch_all(&mut *transaction)
+        )
+        .await
+        .map_err(internal_error)?;
+
+        match new_links {
+            Ok(mut links) => {
+                transaction.commit().await.map_err(internal_error)?;
+
+                tracing::debug!("Created {} new links in bulk", links.len());
+
+                // Postgres does not guarantee that returned rows keep the input order
+                links.sort_by_key(|link| new_link_ids.iter().position(|id| *id == link.id));
+
+                let links: Vec<ShortLink> = links
+                    .into_iter()
+                    .map(|link| ShortLink::new(link, &state.base_url))
+                    .collect();
+
+                return Ok(Json(links).into_response());
+            }
+            Err(err) => match err {
+                Error::Database(db_err) if db_err.kind() == ErrorKind::UniqueViolation => {
+                    // The detail of the error names the first of the colliding ids
+                    let detail = db_err
+                        .try_downcast_ref::<PgDatabaseError>()
+                        .and_then(PgDatabaseError::detail)
+                        .unwrap_or_default();
+
+                    tracing::warn!("Generated ids collide with existing links in attempt {}, retrying: {}", attempt, detail);
+                    increment_counter!("link_id_collision_total");
+                }
+                _ => return Err(internal_error(err))
+            }
+        }
+    }
+
+    tracing::error!("Could not persist new short links in bulk. Exhausted all retries of generating unique ids");
+    increment_counter!("saving_link_impossible_no_unique_id");
+
+    Err(AppError::Internal(ErrorBody::new("internal_error", "Internal server error")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/links",
+    params(ListLinksQuery),
+    responses(
+        (status = 200, description = "Page of links, newest first", body = PaginatedLinks),
+        (status = 422, description = "Invalid pagination")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(http.method = "GET"))]
+pub async fn list_links(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Query(query): Query<ListLinksQuery>,
+) -> Result<Json<PaginatedLinks>, AppError> {
+    let (page, page_size) = Pagination {
+        page: query.page,
+        page_size: query.page_size,
+    }
+    .resolve()?;
+    let offset = i64::from(page - 1) * i64::from(page_size);
+
+    let target_url_pattern = query.q.map(|q| {
+        let escaped_q = q
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+
+        format!("%{}%", escaped_q)
+    });
+
+    let fetch_links_timeout = config.db_query_timeout;
+
+    let links = timed_query(
+        "select_links",
+        fetch_links_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, link_tag_names(id) as "tags!" from links
+            where deleted_at is null and ($1::text is null or target_url ilike $1)
+                and ($4::text is null or exists (
+                    select 1 from link_tags join tags on tags.id = link_tags.tag_id
+                    where link_tags.link_id = links.id and tags.name = $4
+                ))
+                and workspace_id is not distinct from $5
+            order by created_at desc, id
+            limit $2 offset $3
+            "#,
+            target_url_pattern,
+            i64::from(page_size),
+            offset,
+            query.tag,
+            api_key.workspace_id
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let count_links_timeout = config.db_query_timeout;
+
+    let total_count = timed_query(
+        "count_links",
+        count_links_timeout,
+        sqlx::query_scalar!(
+            r#"
+            select count(*) as "total_count!" from links
+            where deleted_at is null and ($1::text is null or target_url ilike $1)
+                and ($2::text is null or exists (
+                    select 1 from link_tags join tags on tags.id = link_tags.tag_id
+                    where link_tags.link_id = links.id and tags.name = $2
+                ))
+                and workspace_id is not distinct from $3
+            "#,
+            target_url_pattern,
+            query.tag,
+            api_key.workspace_id
+        )
+        .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Listed page {} of links with page size {}", page, page_size);
+
+    Ok(Json(PaginatedLinks {
+        items: links,
+        page,
+        page_size,
+        total_count,
+    }))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Id of the short link"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Uuid under which retries within 24 hours get the same response without updating the link again")
+    ),
+    request_body(content = LinkTarget, description = "Replaces the target url, fields left out keep their stored values"),
+    responses(
+        (status = 200, description = "Updated link, or the response to the update made before with the same idempotency key", body = ShortLink),
+        (status = 404, description = "Link not found"),
+        (status = 409, description = "Link is an alias, or idempotency key used for an update that can no longer be replayed"),
+        (status = 412, description = "Link was modified after If-Unmodified-Since"),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Target url malformed or not allowed, webhook url invalid, title too long, tag, cache control, utm param, or robots tag invalid, or idempotency key invalid or used for another link")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, link.target_url = tracing::field::Empty, http.method = "PATCH"))]
+pub async fn update_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+    headers: HeaderMap,
+    ValidatedJson(update_link): ValidatedJson<LinkTarget>,
+) -> Result<ShortLink, AppError> {
+    let url = parse_target_url(&update_link.target_url, &state.blocklist)?;
+    tracing::Span::current().record("link.target_url", url.as_str());
+    let webhook_url =
+        parse_webhook_url(update_link.webhook_url.as_deref())?;
+    // Whether the link may use no-store is checked once its stored values are known
+    let cache_control = parse_cache_control(update_link.cache_control.as_deref(), false)?;
+    let tags = parse_tags(update_link.tags.as_deref())?;
+    let utm_params = parse_utm_params(update_link.utm_params.as_ref())?;
+    let robots_tag = parse_robots_tag(update_link.robots_tag.as_deref())?;
+
+    // Invalid dates are ignored as demanded by RFC 9110, which makes the update unconditional
+    let if_unmodified_since = headers
+        .get(IF_UNMODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .map(|if_unmodified_since| if_unmodified_since.with_timezone(&Utc));
+
+    let update_link_timeout = state.config.db_query_timeout;
+
+    let idempotency_key = idempotency::idempotency_key(&headers)?;
+
+    let mut transaction = audit::begin(&state.db, api_key.id)
+        .await?;
+
+    // Fields left out keep their stored values, so the check of the cache control has to
+    // take those into account. The lock keeps other updates from changing them meanwhile.
+    let stored_link = timed_query(
+        "select_link_for_update",
+        update_link_timeout,
+        sqlx::query!(
+            r#"
+            select is_permanent, cache_control from links
+            where id = $1 and deleted_at is null and workspace_id is not distinct from $2
+            for update
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_optional(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let Some(stored_link) = stored_link else {
+        return Err(AppError::NotFound(ErrorBody::new("link_not_found", "Not found")));
+    };
+
+    parse_cache_control(
+        cache_control.as_deref().or(stored_link.cache_control.as_deref()),
+        update_link.permanent.unwrap_or(stored_link.is_permanent),
+    )?;
+
+    // Keys are only claimed for and replayed to links of the workspace. A retry racing the
+    // original request waits for it to commit and then replays the response it stored.
+    if let Some(key) = idempotency_key {
+        let claimed = idempotency::claim_update(&mut transaction, update_link_timeout, key, api_key.workspace_id, &link_id)
+            .await?;
+
+        if !claimed {
+            drop(transaction);
+
+            let replayed_link = idempotency::find_update(&state.db, update_link_timeout, key, api_key.workspace_id, &link_id)
+                .await?;
+
+            // The key expired or its link was deleted for good in the meantime
+            let Some(short_link) = replayed_link else {
+                return Err(AppError::Conflict(ErrorBody::new(
+                    "idempotency_key_in_use",
+                    "idempotency key was already used for an update that can no longer be replayed",
+                )));
+            };
+
+            tracing::debug!("Returned response of the update made before for idempotency key {}", key);
+
+            return Ok(short_link);
+        }
+    }
+
+    // HTTP dates only have a precision of seconds, so updated_at is truncated the same way
+    // before both are compared. The check is part of the update to not race with other updates.
+    // Tags are only replaced if the link was actually updated and the request contained them.
+    let link = timed_query(
+        "update_link",
+        update_link_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            with updated_link as (
+                update links set target_url = $1, expires_at = coalesce($3, expires_at), is_permanent = coalesce($4, is_permanent),
+                    max_clicks = coalesce($5, max_clicks), webhook_url = coalesce($6, webhook_url), title = coalesce($7, title),
+                    description = coalesce($8, description), cache_control = coalesce($11, cache_control),
+                    utm_params = coalesce($13, utm_params), robots_tag = coalesce($14, robots_tag),
+                    preview_fetched_at = case when target_url = $1 then preview_fetched_at end
+                where id = $2 and deleted_at is null and canonical_id is null
+                    and workspace_id is not distinct from $12
+                    and ($9::timestamptz is null or date_trunc('second', updated_at) <= $9)
+                returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at
+            ), upserted_tags as (
+                insert into tags(name) select unnest($10::text[]) where exists (select 1 from updated_link)
+                on conflict (name) do update set name = excluded.name
+                returning id
+            ), removed_link_tags as (
+                delete from link_tags
+                where $10::text[] is not null
+                    and link_id in (select id from updated_link)
+                    and tag_id not in (select id from upserted_tags)
+            ), inserted_link_tags as (
+                insert into link_tags(link_id, tag_id)
+                select updated_link.id, upserted_tags.id from updated_link, upserted_tags
+                on conflict do nothing
+            )
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, coalesce($10::text[], link_tag_names(id)) as "tags!"
+            from updated_link
+            "#,
+            &url,
+            &link_id,
+            update_link.expires_at,
+            update_link.permanent,
+            update_link.max_clicks,
+            webhook_url,
+            update_link.title,
+            update_link.description,
+            if_unmodified_since,
+            tags.as_deref(),
+            cache_control,
+            api_key.workspace_id,
+            utm_params as Option<UtmParams>,
+            robots_tag
+        )
+        .fetch_optional(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    let Some(link) = link else {
+        let canonical_id = timed_query(
+            "select_link_canonical_id",
+            update_link_timeout,
+            sqlx::query_scalar!(
+                "select canonical_id from links where id = $1 and deleted_at is null and workspace_id is not distinct from $2",
+                &link_id,
+                api_key.workspace_id
+            )
+            .fetch_optional(&state.db),
+        )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+
+        return Err(match canonical_id {
+            Some(Some(_)) => alias_not_updatable(),
+            Some(None) => {
+                tracing::debug!("Rejected update of link with id {}, it was modified in the meantime", link_id);
+
+                AppError::PreconditionFailed(ErrorBody::new(
+                    "link_modified",
+                    "link was modified in the meantime",
+                ))
+            }
+            None => AppError::NotFound(ErrorBody::new("link_not_found", "Not found")),
+        });
+    };
+
+    update_aliases(&state, &mut transaction, &link_id, &link.target_url, link.utm_params.as_ref())
+        .await?;
+
+    let short_link = ShortLink::new(link, &state.base_url);
+
+    if let Some(key) = idempotency_key {
+        idempotency::remember_update(&mut transaction, update_link_timeout, key, api_key.workspace_id, &short_link)
+            .await?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(internal_error)?;
+
+    invalidate_cached_link(&state, &link_id).await;
+
+    tracing::debug!("Updated link with id {}, now targeting {}", link_id, url);
+
+    Ok(short_link)
+}
+
+#[utoipa::path(
+    put,
+    path = "/{id}",
+    params(("id" = String, Path, description = "Id of the short link")),
+    request_body = LinkTarget,
+    responses(
+        (status = 200, description = "Replaced existing link", body = ShortLink),
+        (status = 201, description = "Created link", body = ShortLink),
+        (status = 409, description = "Link is an alias"),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Id malformed or reserved, target url malformed or not allowed, webhook url invalid, title too long, or tag, cache control, utm param, or robots tag invalid")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, link.target_url = tracing::field::Empty, http.method = "PUT"))]
+pub async fn upsert_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+    headers: HeaderMap,
+    ValidatedJson(desired_link): ValidatedJson<LinkTarget>,
+) -> Result<(StatusCode, ShortLink), AppError> {
+    if !custom_id_regex().is_match(&link_id) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new("id_malformed", "id malformed")));
+    }
+
+    if state.link_id_blacklist.contains(&link_id) {
+        return Err(AppError::UnprocessableEntity(ErrorBody::new("id_reserved", "id is reserved")));
+    }
+
+    let url = parse_target_url(&desired_link.target_url, &state.blocklist)?;
+    tracing::Span::current().record("link.target_url", url.as_str());
+    let webhook_url =
+        parse_webhook_url(desired_link.webhook_url.as_deref())?;
+    let cache_control = parse_cache_control(desired_link.cache_control.as_deref(), desired_link.permanent.unwrap_or(false))?;
+    // A declared link carries exactly the declared tags
+    let tags = parse_tags(desired_link.tags.as_deref())?
+        .unwrap_or_default();
+    let utm_params = parse_utm_params(desired_link.utm_params.as_ref())?;
+    let robots_tag = parse_robots_tag(desired_link.robots_tag.as_deref())?;
+
+    let upsert_link_timeout = state.config.db_query_timeout;
+    let creator = LinkCreator::new(&headers, api_key);
+
+    let mut transaction = audit::begin(&state.db, creator.api_key_id)
+        .await?;
+
+    // xmax is only zero for freshly inserted rows, which is the cheapest way to
+    // tell an insert from an update within the same statement. Declaring a link
+    // also brings it back if it was deleted before, but keeps who created it.
+    let upserted_link = timed_query(
+        "upsert_link",
+        upsert_link_timeout,
+        sqlx::query!(
+            r#"
+            with upserted_link as (
+                insert into links(
+                    id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control,
+                    workspace_id, utm_params, robots_tag, creator_user_agent, created_by_key_id
+                )
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $10, $11, $12, $13, $14, $15)
+                on conflict (id) do update set
+                    target_url = excluded.target_url,
+                    expires_at = excluded.expires_at,
+                    is_permanent = excluded.is_permanent,
+                    max_clicks = excluded.max_clicks,
+                    webhook_url = excluded.webhook_url,
+                    title = excluded.title,
+                    description = excluded.description,
+                    cache_control = excluded.cache_control,
+                    utm_params = excluded.utm_params,
+                    robots_tag = excluded.robots_tag,
+                    preview_fetched_at = case when links.target_url = excluded.target_url then links.preview_fetched_at end,
+                    deleted_at = null
+                where links.canonical_id is null and links.workspace_id is not distinct from excluded.workspace_id
+                returning
+                    id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at,
+                    (xmax = 0) as inserted
+            ), upserted_tags as (
+                insert into tags(name) select unnest($9::text[])
+                on conflict (name) do update set name = excluded.name
+                returning id
+            ), removed_link_tags as (
+                delete from link_tags
+                where link_id in (select id from upserted_link) and tag_id not in (select id from upserted_tags)
+            ), inserted_link_tags as (
+                insert into link_tags(link_id, tag_id)
+                select upserted_link.id, upserted_tags.id from upserted_link, upserted_tags
+                on conflict do nothing
+            )
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, inserted as "inserted!", $9::text[] as "tags!"
+            from upserted_link
+            "#,
+            &link_id,
+            &url,
+            desired_link.expires_at,
+            desired_link.permanent.unwrap_or(false),
+            desired_link.max_clicks,
+            webhook_url,
+            desired_link.title,
+            desired_link.description,
+            &tags,
+            cache_control,
+            creator.workspace_id,
+            utm_params as Option<UtmParams>,
+            robots_tag,
+            creator.user_agent,
+            creator.api_key_id
+        )
+        .fetch_optional(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
 
-use crate::utils::internal_error;
+    // Only an existing alias or a link of another workspace leaves the conflicting row untouched
+    let Some(upserted_link) = upserted_link else {
+        let is_own_link = timed_query(
+            "select_link_workspace",
+            upsert_link_timeout,
+            sqlx::query_scalar!(
+                r#"select workspace_id is not distinct from $2 as "is_own_link!" from links where id = $1"#,
+                &link_id,
+                api_key.workspace_id
+            )
+            .fetch_optional(&state.db),
+        )
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
 
-const DEFAULT_CACHE_CONTROL_HEADER_VALUE: &str =
-    "public, max-age=300, s-maxage=300, stale-while-revalidate=300, stale-if-error=300";
+        return Err(match is_own_link {
+            Some(true) => alias_not_updatable(),
+            _ => AppError::Conflict(ErrorBody::new("id_taken", "id already taken")),
+        });
+    };
 
-#[derive(serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Link {
-    pub id: String,
-    pub target_url: String,
-}
+    update_aliases(
+        &state,
+        &mut transaction,
+        &link_id,
+        &upserted_link.target_url,
+        upserted_link.utm_params.as_ref(),
+    )
+        .await?;
 
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct LinkTarget {
-    pub target_url: String,
-}
+    transaction
+        .commit()
+        .await
+        .map_err(internal_error)?;
 
-#[derive(serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CountedLinkStatistic {
-    pub amount: Option<i64>,
-    pub referer: Option<String>,
-    pub user_agent: Option<String>,
-}
+    invalidate_cached_link(&state, &link_id).await;
 
-fn generate_id() -> String {
-    let random_number = rand::thread_rng().gen_range(0..u32::MAX);
-    general_purpose::URL_SAFE_NO_PAD.encode(random_number.to_string())
-}
+    let link = Link {
+        id: upserted_link.id,
+        target_url: upserted_link.target_url,
+        expires_at: upserted_link.expires_at,
+        is_permanent: upserted_link.is_permanent,
+        max_clicks: upserted_link.max_clicks,
+        webhook_url: upserted_link.webhook_url,
+        title: upserted_link.title,
+        description: upserted_link.description,
+        cache_control: upserted_link.cache_control,
+        robots_tag: upserted_link.robots_tag,
+        utm_params: upserted_link.utm_params,
+        signed: upserted_link.signed,
+        active: upserted_link.active,
+        tags: upserted_link.tags,
+        created_at: upserted_link.created_at,
+        updated_at: upserted_link.updated_at,
+        deleted_at: upserted_link.deleted_at,
+    };
+
+    let status = if upserted_link.inserted {
+        tracing::debug!("Created link with id {} targeting {} by upsert", link_id, url);
 
-pub async fn health() -> impl IntoResponse {
-    (StatusCode::OK, "Service is healthy")
+        StatusCode::CREATED
+    } else {
+        tracing::debug!("Replaced link with id {}, now targeting {}", link_id, url);
+
+        StatusCode::OK
+    };
+
+    Ok((status, ShortLink::new(link, &state.base_url)))
 }
 
-pub async fn redirect(
-    State(pool): State<PgPool>,
-    Path(requested_link): Path<String>,
-    headers: HeaderMap,
-) -> Result<Response, (StatusCode, String)> {
-    let select_timeout = tokio::time::Duration::from_millis(300);
+async fn insert_alias(
+    state: &AppState,
+    alias_id: &str,
+    link_id: &str,
+    creator: &LinkCreator,
+) -> Result<Result<Option<Link>, Error>, AppError> {
+    let mut transaction = audit::begin(&state.db, creator.api_key_id).await?;
 
-    let link = tokio::time::timeout(
-        select_timeout,
+    // Aliases of aliases point to the link at the end of the chain, so that following
+    // an alias never takes more than one hop
+    let alias = timed_query(
+        "insert_alias",
+        state.config.db_query_timeout,
         sqlx::query_as!(
             Link,
-            "select id, target_url from links where id = $1",
-            requested_link
+            r#"
+            insert into links(id, target_url, utm_params, canonical_id, creator_user_agent, created_by_key_id, workspace_id)
+            select $1, target_url, utm_params, coalesce(canonical_id, id), $3, $4, workspace_id from links
+            where id = $2 and deleted_at is null and workspace_id is not distinct from $5
+            returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, array[]::text[] as "tags!"
+            "#,
+            alias_id,
+            link_id,
+            creator.user_agent,
+            creator.api_key_id,
+            creator.workspace_id
         )
-            .fetch_optional(&pool),
+        .fetch_optional(&mut *transaction)
     )
-        .await
-        .map_err(internal_error)?
-        .map_err(internal_error)?
-        .ok_or_else(|| "Not found".to_string())
-        .map_err(|err| (StatusCode::NOT_FOUND, err))?;
+    .await
+    .map_err(internal_error)?;
 
-    tracing::debug!(
-        "Redirecting link id {} to {}",
-        requested_link,
-        link.target_url
-    );
+    Ok(match alias {
+        Ok(alias) => transaction.commit().await.map(|_| alias),
+        Err(err) => Err(err),
+    })
+}
 
-    let referer_header = headers
-        .get("referer")
-        .map(|value| value.to_str().unwrap_or_default().to_string());
+#[utoipa::path(
+    post,
+    path = "/{id}/alias",
+    params(("id" = String, Path, description = "Id of the short link the alias points to")),
+    request_body = CreateAliasRequest,
+    responses(
+        (status = 201, description = "Created alias, which always redirects to the target url of the link", body = ShortLink),
+        (status = 404, description = "Link not found"),
+        (status = 409, description = "Custom id already taken"),
+        (status = 415, description = "Body is not json"),
+        (status = 422, description = "Custom id malformed or reserved")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, alias.id = tracing::field::Empty, http.method = "POST"))]
+pub async fn create_alias(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+    headers: HeaderMap,
+    Json(new_alias): Json<CreateAliasRequest>,
+) -> Result<(StatusCode, ShortLink), AppError> {
+    let creator = LinkCreator::new(&headers, api_key);
 
-    let user_agent_header = headers
-        .get("user-agent")
-        .map(|value| value.to_str().unwrap_or_default().to_string());
+    let not_found = || AppError::NotFound(ErrorBody::new("link_not_found", "Not found"));
+
+    if let Some(custom_id) = &new_alias.custom_id {
+        if !custom_id_regex().is_match(custom_id) {
+            return Err(AppError::UnprocessableEntity(ErrorBody::new("custom_id_malformed", "custom id malformed")));
+        }
+
+        if state.link_id_blacklist.contains(custom_id) {
+            return Err(custom_id_reserved());
+        }
+
+        return match insert_alias(&state, custom_id, &link_id, &creator).await? {
+            Ok(Some(alias)) => {
+                tracing::Span::current().record("alias.id", custom_id.as_str());
+                tracing::debug!("Created alias with custom id {} for link with id {}", custom_id, link_id);
+
+                Ok((StatusCode::CREATED, ShortLink::new(alias, &state.base_url)))
+            }
+            Ok(None) => Err(not_found()),
+            Err(Error::Database(db_err)) if db_err.kind() == ErrorKind::UniqueViolation => {
+                Err(AppError::Conflict(ErrorBody::new("custom_id_taken", "custom id already taken")))
+            }
+            Err(err) => Err(internal_error(err)),
+        };
+    }
+
+    for attempt in 1..=3 {
+        let alias_id = generate_id(&state);
+
+        match insert_alias(&state, &alias_id, &link_id, &creator).await? {
+            Ok(Some(alias)) => {
+                tracing::Span::current().record("alias.id", alias_id.as_str());
+                tracing::debug!("Created alias with id {} for link with id {}", alias_id, link_id);
+
+                return Ok((StatusCode::CREATED, ShortLink::new(alias, &state.base_url)));
+            }
+            Ok(None) => return Err(not_found()),
+            Err(Error::Database(db_err)) if db_err.kind() == ErrorKind::UniqueViolation => {
+                record_id_collision(&alias_id, attempt);
+            }
+            Err(err) => return Err(internal_error(err)),
+        }
+    }
+
+    tracing::error!("Could not persist new alias. Exhausted all retries of generating a unique id");
+    increment_counter!("saving_link_impossible_no_unique_id");
+
+    Err(AppError::Internal(ErrorBody::new("internal_error", "Internal server error")))
+}
 
-    let insert_statistics_timeout = tokio::time::Duration::from_millis(300);
+async fn insert_copy(
+    state: &AppState,
+    copy_id: &str,
+    link_id: &str,
+    creator: &LinkCreator,
+) -> Result<Result<Option<Link>, Error>, AppError> {
+    let mut transaction = audit::begin(&state.db, creator.api_key_id).await?;
 
-    let saved_statistic = tokio::time::timeout(
-        insert_statistics_timeout,
-        sqlx::query(
+    // The tags of the copy are the ones of the link, so they are looked up from the link
+    let copy = timed_query(
+        "insert_copy",
+        state.config.db_query_timeout,
+        sqlx::query_as!(
+            Link,
             r#"
-                insert into link_statistics(link_id, referer, user_agent)
-                values($1, $2, $3)
-                "#,
+            with copied_link as (
+                insert into links(id, target_url, utm_params, expires_at, is_permanent, max_clicks, robots_tag, creator_user_agent, created_by_key_id, workspace_id)
+                select $1, target_url, utm_params, expires_at, is_permanent, max_clicks, robots_tag, $3, $4, workspace_id from links
+                where id = $2 and deleted_at is null and workspace_id is not distinct from $5
+                returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at
+            ), copied_link_tags as (
+                insert into link_tags(link_id, tag_id)
+                select copied_link.id, link_tags.tag_id from copied_link, link_tags where link_tags.link_id = $2
+            )
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, link_tag_names($2) as "tags!"
+            from copied_link
+            "#,
+            copy_id,
+            link_id,
+            creator.user_agent,
+            creator.api_key_id,
+            creator.workspace_id
         )
-            .bind(&requested_link)
-            .bind(&referer_header)
-            .bind(&user_agent_header)
-            .execute(&pool),
+        .fetch_optional(&mut *transaction)
     )
-    .await;
-
-    match saved_statistic {
-        Err(elapsed) => tracing::error!("Saving new link click resulted in a timeout: {}", elapsed),
-        Ok(Err(err)) => tracing::error!(
-            "Saving a new link click failed with the following error: {}",
-            err
-        ),
-        _ => tracing::debug!(
-            "Persisted new link click for link with id {}, referer {}, and user_agent {}",
-            requested_link,
-            referer_header.unwrap_or_default(),
-            user_agent_header.unwrap_or_default()
-        ),
-    };
+    .await
+    .map_err(internal_error)?;
 
-    Ok(Response::builder()
-        .status(StatusCode::TEMPORARY_REDIRECT)
-        .header("Location", link.target_url)
-        .header("Cache-Control", DEFAULT_CACHE_CONTROL_HEADER_VALUE)
-        .body(Body::empty())
-        .expect("This response should always be constructable"))
+    Ok(match copy {
+        Ok(copy) => transaction.commit().await.map(|_| copy),
+        Err(err) => Err(err),
+    })
 }
 
-pub async fn create_link(
-    State(pool): State<PgPool>,
-    Json(new_link): Json<LinkTarget>,
-) -> Result<Json<Link>, (StatusCode, String)> {
-    let url = Url::parse(&new_link.target_url)
-        .map_err(|_| (StatusCode::CONFLICT, "url malformed".into()))?
-        .to_string();
+#[utoipa::path(
+    post,
+    path = "/{id}/copy",
+    params(("id" = String, Path, description = "Id of the short link to copy")),
+    request_body(content = Option<CopyLinkRequest>, description = "Optional, the id of the copy is generated without it"),
+    responses(
+        (status = 201, description = "Created copy with the target url, utm params, expiry, click limit and tags of the link", body = ShortLink),
+        (status = 400, description = "Body is no valid json"),
+        (status = 404, description = "Link not found"),
+        (status = 409, description = "Custom id already taken"),
+        (status = 422, description = "Custom id malformed or reserved")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, copy.id = tracing::field::Empty, http.method = "POST"))]
+pub async fn copy_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+    headers: HeaderMap,
+    OptionalJson(copy): OptionalJson<CopyLinkRequest>,
+) -> Result<(StatusCode, ShortLink), AppError> {
+    let creator = LinkCreator::new(&headers, api_key);
+    let copy = copy.unwrap_or_default();
 
-    let insert_link_timeout = tokio::time::Duration::from_millis(300);
+    let not_found = || AppError::NotFound(ErrorBody::new("link_not_found", "Not found"));
 
-    for _ in 1..=3 {
-        let new_link_id = generate_id();
+    if let Some(custom_id) = &copy.custom_id {
+        if !custom_id_regex().is_match(custom_id) {
+            return Err(AppError::UnprocessableEntity(ErrorBody::new("custom_id_malformed", "custom id malformed")));
+        }
 
-        let new_link = tokio::time::timeout(
-                insert_link_timeout,
-                sqlx::query_as!(
-                Link,
-                r#"
-                with inserted_link as (
-                    insert into links(id, target_url)
-                    values ($1, $2)
-                    returning id, target_url
-                )
-                select id, target_url from inserted_link
-                "#,
-                &new_link_id,
-                &url
-            )
-            .fetch_one(&pool)
-        )
-        .await
-        .map_err(internal_error)?;
+        if state.link_id_blacklist.contains(custom_id) {
+            return Err(custom_id_reserved());
+        }
 
-        match new_link {
-            Ok(link) => {
-                tracing::debug!("Created new link with id {} targeting {}", new_link_id, url);
+        return match insert_copy(&state, custom_id, &link_id, &creator).await? {
+            Ok(Some(copy)) => {
+                tracing::Span::current().record("copy.id", custom_id.as_str());
+                tracing::debug!("Copied link with id {} to custom id {}", link_id, custom_id);
 
-                return Ok(Json(link))
+                Ok((StatusCode::CREATED, ShortLink::new(copy, &state.base_url)))
             }
-            Err(err) => match err {
-                Error::Database(db_err) if db_err.kind() == ErrorKind::UniqueViolation => {}
-                _ => return Err(internal_error(err))
+            Ok(None) => Err(not_found()),
+            Err(Error::Database(db_err)) if db_err.kind() == ErrorKind::UniqueViolation => {
+                Err(AppError::Conflict(ErrorBody::new("custom_id_taken", "custom id already taken")))
+            }
+            Err(err) => Err(internal_error(err)),
+        };
+    }
+
+    for attempt in 1..=3 {
+        let copy_id = generate_id(&state);
+
+        match insert_copy(&state, &copy_id, &link_id, &creator).await? {
+            Ok(Some(copy)) => {
+                tracing::Span::current().record("copy.id", copy_id.as_str());
+                tracing::debug!("Copied link with id {} to id {}", link_id, copy_id);
+
+                return Ok((StatusCode::CREATED, ShortLink::new(copy, &state.base_url)));
             }
+            Ok(None) => return Err(not_found()),
+            Err(Error::Database(db_err)) if db_err.kind() == ErrorKind::UniqueViolation => {
+                record_id_collision(&copy_id, attempt);
+            }
+            Err(err) => return Err(internal_error(err)),
         }
     }
 
-    tracing::error!("Could not persist new short link. Exhausted all retries of generating a unique id");
+    tracing::error!("Could not persist copy of link. Exhausted all retries of generating a unique id");
     increment_counter!("saving_link_impossible_no_unique_id");
 
-    Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".into()))
+    Err(AppError::Internal(ErrorBody::new("internal_error", "Internal server error")))
 }
 
-pub async fn update_link(
-    State(pool): State<PgPool>,
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 204, description = "Link deleted, its statistics are kept"),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "DELETE"))]
+pub async fn delete_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
     Path(link_id): Path<String>,
-    Json(update_link): Json<LinkTarget>,
-) -> Result<Json<Link>, (StatusCode, String)> {
-    let url = Url::parse(&update_link.target_url)
-        .map_err(|_| (StatusCode::CONFLICT, "url malformed".into()))?
-        .to_string();
+) -> Result<StatusCode, AppError> {
+    let delete_link_timeout = state.config.db_query_timeout;
 
-    let update_link_timeout = tokio::time::Duration::from_millis(300);
+    let mut transaction = audit::begin(&state.db, api_key.id).await?;
 
-    let link = tokio::time::timeout(
+    let deleted_link = timed_query(
+        "delete_link",
+        delete_link_timeout,
+        sqlx::query!(
+            "update links set deleted_at = now() where id = $1 and deleted_at is null and workspace_id is not distinct from $2",
+            &link_id,
+            api_key.workspace_id
+        )
+        .execute(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    if deleted_link.rows_affected() == 0 {
+        return Err(AppError::NotFound(ErrorBody::new("link_not_found", "Not found")));
+    }
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    invalidate_cached_link(&state, &link_id).await;
+
+    tracing::debug!("Deleted link with id {}", link_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_link_active(
+    state: &AppState,
+    api_key: AuthenticatedApiKey,
+    link_id: &str,
+    active: bool,
+) -> Result<ShortLink, AppError> {
+    let update_link_timeout = state.config.db_query_timeout;
+
+    let mut transaction = audit::begin(&state.db, api_key.id).await?;
+
+    let link = timed_query(
+        "set_link_active",
         update_link_timeout,
         sqlx::query_as!(
             Link,
             r#"
             with updated_link as (
-                update links set target_url = $1 where id = $2
-                returning id, target_url
+                update links set active = $2 where id = $1 and deleted_at is null and workspace_id is not distinct from $3
+                returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at
             )
-            select id, target_url
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, link_tag_names(id) as "tags!"
             from updated_link
             "#,
-            &url,
-            &link_id
+            link_id,
+            active,
+            api_key.workspace_id
         )
-        .fetch_one(&pool),
+        .fetch_optional(&mut *transaction),
     )
     .await
     .map_err(internal_error)?
-    .map_err(internal_error)?;
+    .map_err(internal_error)?
+    .ok_or_else(|| AppError::NotFound(ErrorBody::new("link_not_found", "Not found")))?;
 
-    tracing::debug!("Updated link with id {}, now targeting {}", link_id, url);
+    transaction.commit().await.map_err(internal_error)?;
+
+    invalidate_cached_link(state, link_id).await;
+
+    Ok(ShortLink::new(link, &state.base_url))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/deactivate",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Deactivated link", body = ShortLink),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "POST"))]
+pub async fn deactivate_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<ShortLink, AppError> {
+    let link = set_link_active(&state, api_key, &link_id, false).await?;
+
+    tracing::debug!("Deactivated link with id {}", link_id);
+
+    Ok(link)
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/activate",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Activated link", body = ShortLink),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "POST"))]
+pub async fn activate_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<ShortLink, AppError> {
+    let link = set_link_active(&state, api_key, &link_id, true).await?;
+
+    tracing::debug!("Activated link with id {}", link_id);
+
+    Ok(link)
+}
 
-    Ok(Json(link))
+#[utoipa::path(
+    post,
+    path = "/{id}/restore",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Restored link", body = ShortLink),
+        (status = 404, description = "No deleted link with this id")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "POST"))]
+pub async fn restore_link(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<ShortLink, AppError> {
+    let restore_link_timeout = state.config.db_query_timeout;
+
+    let mut transaction = audit::begin(&state.db, api_key.id).await?;
+
+    let link = timed_query(
+        "restore_link",
+        restore_link_timeout,
+        sqlx::query_as!(
+            Link,
+            r#"
+            with restored_link as (
+                update links set deleted_at = null where id = $1 and deleted_at is not null and workspace_id is not distinct from $2
+                returning id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params, signed, active, created_at, updated_at, deleted_at
+            )
+            select id, target_url, expires_at, is_permanent, max_clicks, webhook_url, title, description, cache_control, robots_tag, utm_params as "utm_params: UtmParams", signed, active, created_at, updated_at, deleted_at, link_tag_names(id) as "tags!"
+            from restored_link
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_optional(&mut *transaction),
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?
+    .ok_or_else(|| AppError::NotFound(ErrorBody::new("link_not_found", "Not found")))?;
+
+    transaction.commit().await.map_err(internal_error)?;
+
+    invalidate_cached_link(&state, &link_id).await;
+
+    tracing::debug!("Restored link with id {}", link_id);
+
+    Ok(ShortLink::new(link, &state.base_url))
 }
 
+#[utoipa::path(
+    get,
+    path = "/{id}/statistics",
+    params(("id" = String, Path, description = "Id of the short link"), Pagination),
+    responses(
+        (status = 200, description = "Page of click statistics grouped by referer and user agent", body = PaginatedStatistics),
+        (status = 422, description = "Invalid pagination")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
 pub async fn get_link_statistics(
     State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
     Path(link_id): Path<String>,
-) -> Result<Json<Vec<CountedLinkStatistic>>, (StatusCode, String)> {
-    let fetch_statistics_timeout = tokio::time::Duration::from_millis(300);
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<PaginatedStatistics>, AppError> {
+    let (page, page_size) = pagination.resolve()?;
+    let offset = i64::from(page - 1) * i64::from(page_size);
+
+    let fetch_statistics_timeout = config.db_query_timeout;
 
-    let statistics = tokio::time::timeout(
+    // Links of other workspaces have no statistics for the caller, just like unknown links
+    let statistics = timed_query(
+        "select_statistics",
         fetch_statistics_timeout,
         sqlx::query_as!(
             CountedLinkStatistic,
             r#"
-            select count(*) as amount, referer, user_agent from link_statistics group by link_id, referer, user_agent having link_id = $1
+            select link_id, count(*) as amount, referer, user_agent, locale from link_statistics group by link_id, referer, user_agent, locale having link_id = $1
+                and link_id in (select id from links where workspace_id is not distinct from $4)
+            order by amount desc, referer, user_agent, locale
+            limit $2 offset $3
             "#,
-            &link_id
+            &link_id,
+            i64::from(page_size),
+            offset,
+            api_key.workspace_id
         )
         .fetch_all(&pool)
     )
@@ -231,7 +3010,239 @@ pub async fn get_link_statistics(
     .map_err(internal_error)?
     .map_err(internal_error)?;
 
+    let count_statistics_timeout = config.db_query_timeout;
+
+    let total_count = timed_query(
+        "count_statistics",
+        count_statistics_timeout,
+        sqlx::query_scalar!(
+            r#"
+            select count(*) as "total_count!" from (
+                select 1 from link_statistics group by link_id, referer, user_agent, locale having link_id = $1
+                    and link_id in (select id from links where workspace_id is not distinct from $2)
+            ) as grouped_statistics
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_one(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
     tracing::debug!("Statistics for link with id {} requested", link_id);
 
-    Ok(Json(statistics))
+    Ok(Json(PaginatedStatistics {
+        items: statistics,
+        page,
+        page_size,
+        total_count,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/statistics/summary",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Aggregated totals of all clicks", body = LinkStatisticsSummary),
+        (status = 404, description = "Link not found")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_link_statistics_summary(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<Json<LinkStatisticsSummary>, AppError> {
+    let fetch_summary_timeout = config.db_query_timeout;
+
+    let summary = timed_query(
+        "select_statistics_summary",
+        fetch_summary_timeout,
+        sqlx::query_as!(
+            LinkStatisticsSummary,
+            r#"
+            select
+                count(link_statistics.link_id) as "total_clicks!",
+                count(distinct referer) as "unique_referers!",
+                count(distinct user_agent) as "unique_user_agents!",
+                min(clicked_at) as first_click_at,
+                max(clicked_at) as last_click_at
+            from links
+            left join link_statistics on link_statistics.link_id = links.id
+            where links.id = $1 and links.workspace_id is not distinct from $2
+            group by links.id
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_optional(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    // Links without clicks are summed up with zeros, but unknown links have nothing to sum up
+    let Some(summary) = summary else {
+        return Err(AppError::NotFound(ErrorBody::new("link_not_found", "Not found")));
+    };
+
+    tracing::debug!("Statistics summary for link with id {} requested", link_id);
+
+    Ok(Json(summary))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/statistics/timeseries",
+    params(("id" = String, Path, description = "Id of the short link"), TimeseriesQuery),
+    responses(
+        (status = 200, description = "Click counts per time bucket", body = Vec<ClickBucket>),
+        (status = 422, description = "Invalid time range")
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_link_statistics_timeseries(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+    Query(query): Query<TimeseriesQuery>,
+) -> Result<Json<Vec<ClickBucket>>, AppError> {
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(AppError::UnprocessableEntity(ErrorBody::new("invalid_time_range", "from must not be after to")));
+        }
+    }
+
+    let bucket = query.bucket.unwrap_or(TimeseriesBucket::Day);
+
+    let fetch_timeseries_timeout = config.db_query_timeout;
+
+    let timeseries = timed_query(
+        "select_statistics_timeseries",
+        fetch_timeseries_timeout,
+        sqlx::query_as!(
+            ClickBucket,
+            r#"
+            select date_trunc($2, clicked_at) as "bucket!", count(*) as "count!"
+            from link_statistics
+            where link_id = $1
+              and link_id in (select id from links where workspace_id is not distinct from $5)
+              and ($3::timestamptz is null or clicked_at >= $3)
+              and ($4::timestamptz is null or clicked_at < $4)
+            group by 1
+            order by 1
+            "#,
+            &link_id,
+            bucket.as_str(),
+            query.from,
+            query.to,
+            api_key.workspace_id
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Timeseries statistics for link with id {} requested", link_id);
+
+    Ok(Json(timeseries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/statistics/ips",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Click counts per hashed visitor ip", body = Vec<IpStatistic>)
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_link_statistics_ips(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<Json<Vec<IpStatistic>>, AppError> {
+    let fetch_ips_timeout = config.db_query_timeout;
+
+    let ips = timed_query(
+        "select_statistics_ips",
+        fetch_ips_timeout,
+        sqlx::query_as!(
+            IpStatistic,
+            r#"
+            select ip_hash as "ip_hash!", count(*) as "count!"
+            from link_statistics
+            where link_id = $1 and ip_hash is not null
+              and link_id in (select id from links where workspace_id is not distinct from $2)
+            group by ip_hash
+            order by 2 desc, 1
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Ip statistics for link with id {} requested", link_id);
+
+    Ok(Json(ips))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/statistics/devices",
+    params(("id" = String, Path, description = "Id of the short link")),
+    responses(
+        (status = 200, description = "Click counts per browser and operating system, most clicks first", body = Vec<DeviceStatistic>)
+    ),
+    security(("api_key" = []))
+)]
+#[tracing::instrument(skip_all, fields(link.id = %link_id, http.method = "GET"))]
+pub async fn get_link_statistics_devices(
+    State(pool): State<PgPool>,
+    State(config): State<Config>,
+    Extension(api_key): Extension<AuthenticatedApiKey>,
+    Path(link_id): Path<String>,
+) -> Result<Json<Vec<DeviceStatistic>>, AppError> {
+    let fetch_devices_timeout = config.db_query_timeout;
+
+    // Clicks from before user agents were parsed are grouped with the unrecognized ones
+    let devices = timed_query(
+        "select_statistics_devices",
+        fetch_devices_timeout,
+        sqlx::query_as!(
+            DeviceStatistic,
+            r#"
+            select browser, os, count(*) as "count!"
+            from link_statistics
+            where link_id = $1
+              and link_id in (select id from links where workspace_id is not distinct from $2)
+            group by browser, os
+            order by 3 desc, 1, 2
+            "#,
+            &link_id,
+            api_key.workspace_id
+        )
+        .fetch_all(&pool)
+    )
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    tracing::debug!("Device statistics for link with id {} requested", link_id);
+
+    Ok(Json(devices))
 }
@@ -0,0 +1,36 @@
+use axum::extract::Request;
+use axum::http::header::{
+    CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use axum::http::{HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+
+const CONTENT_SECURITY_POLICY_HEADER_VALUE: &str = "default-src 'none'";
+
+/// The Swagger UI and the GraphQL playground are the only parts of the service that
+/// render html and they need their own scripts and styles, which the strict policy
+/// would block.
+const DOCS_PATH_PREFIX: &str = "/docs";
+const GRAPHQL_PLAYGROUND_PATH: &str = "/graphql";
+
+pub async fn security_headers(req: Request, next: Next) -> Response {
+    let is_docs = req.uri().path().starts_with(DOCS_PATH_PREFIX)
+        || (req.method() == Method::GET && req.uri().path() == GRAPHQL_PLAYGROUND_PATH);
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+
+    if !is_docs {
+        headers.insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(CONTENT_SECURITY_POLICY_HEADER_VALUE),
+        );
+    }
+
+    response
+}
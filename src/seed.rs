@@ -0,0 +1,42 @@
+use std::error::Error;
+
+use sqlx::postgres::PgPoolOptions;
+
+use crate::auth::hash_api_key;
+use crate::config::Config;
+
+/// Stores the hash of `INITIAL_API_KEY` as the global api key, so that new deployments
+/// can be set up without writing SQL, e.g. from an init container. The schema is
+/// migrated first, as this usually runs before the service ever started. Returns the
+/// key clients have to send.
+pub async fn seed_global_api_key() -> Result<String, Box<dyn Error>> {
+    let api_key = std::env::var("INITIAL_API_KEY")
+        .ok()
+        .filter(|api_key| !api_key.is_empty())
+        .ok_or("INITIAL_API_KEY must be set to seed the global api key")?;
+
+    let db_url = std::env::var("DATABASE_URL").map_err(|_| "DATABASE_URL is a required environment variable")?;
+
+    let config = Config::from_env()?;
+
+    let db = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(config.db_connect_timeout)
+        .connect_with(config.db_connect_options(&db_url)?)
+        .await?;
+
+    sqlx::migrate!().run(&db).await?;
+
+    sqlx::query!(
+        r#"
+        insert into settings (id, encrypted_global_api_key) values ($1, $2)
+        on conflict (id) do update set encrypted_global_api_key = excluded.encrypted_global_api_key
+        "#,
+        "DEFAULT_SETTINGS",
+        hash_api_key(&api_key, config.key_algorithm)
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(api_key)
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use tokio::signal;
+
+/// Counts the requests that are currently being processed, so that shutdown
+/// can report how many of them still had to be drained.
+#[derive(Clone, Default)]
+pub struct InFlightRequests(Arc<AtomicUsize>);
+
+impl InFlightRequests {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+struct InFlightGuard(InFlightRequests);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        (self.0).0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn track_in_flight_requests(
+    State(in_flight_requests): State<InFlightRequests>,
+    req: Request,
+    next: Next,
+) -> Response {
+    in_flight_requests.0.fetch_add(1, Ordering::SeqCst);
+
+    // A guard makes sure the counter is decremented even if the request future gets dropped
+    let _guard = InFlightGuard(in_flight_requests);
+
+    next.run(req).await
+}
+
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Could not install the Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Could not install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
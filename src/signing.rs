@@ -0,0 +1,35 @@
+use base64::engine::general_purpose;
+use base64::Engine;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(secret: &str, link_id: &str, expires_at: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC should accept keys of any length");
+    mac.update(format!("{}:{}", link_id, expires_at).as_bytes());
+
+    mac
+}
+
+/// Builds the url of a signed link, which is valid until `expires_at` as a unix timestamp.
+pub fn signed_url(secret: &str, short_url: &str, link_id: &str, expires_at: i64) -> String {
+    let signature = general_purpose::URL_SAFE_NO_PAD.encode(mac(secret, link_id, expires_at).finalize().into_bytes());
+
+    format!("{}?sig={}&exp={}", short_url, signature, expires_at)
+}
+
+/// The expiry is part of the signed message, so it cannot be extended without
+/// invalidating the signature. Signatures are compared in constant time.
+pub fn verify(secret: &str, link_id: &str, expires_at: i64, signature: &str) -> bool {
+    if expires_at < Utc::now().timestamp() {
+        return false;
+    }
+
+    let Ok(signature) = general_purpose::URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+
+    mac(secret, link_id, expires_at).verify_slice(&signature).is_ok()
+}
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::extract::FromRef;
+use moka::future::Cache;
+use moka::Expiry;
+use sqlx::PgPool;
+
+use crate::blocklist::Blocklist;
+use crate::config::Config;
+use crate::maintenance::MaintenanceMode;
+use crate::routes::Link;
+
+/// Time to live of cached links, which can be changed while the service is running
+#[derive(Clone)]
+pub struct LinkCacheTtl(Arc<AtomicU64>);
+
+impl LinkCacheTtl {
+    pub fn new(ttl: Duration) -> Self {
+        Self(Arc::new(AtomicU64::new(ttl.as_secs())))
+    }
+
+    pub fn get(&self) -> Duration {
+        Duration::from_secs(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, ttl: Duration) {
+        self.0.store(ttl.as_secs(), Ordering::Relaxed);
+    }
+}
+
+/// Reads the ttl whenever a link gets cached, so that a changed ttl applies to all
+/// links cached from then on. Links that are already cached keep their expiry.
+impl Expiry<String, Link> for LinkCacheTtl {
+    fn expire_after_create(&self, _key: &String, _value: &Link, _created_at: Instant) -> Option<Duration> {
+        Some(self.get())
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: PgPool,
+    pub base_url: String,
+    pub link_cache: Cache<String, Link>,
+    pub link_cache_ttl: LinkCacheTtl,
+    pub redis: Option<deadpool_redis::Pool>,
+    pub blocklist: Blocklist,
+    pub id_length: usize,
+    /// Ids that are neither generated nor accepted as custom ids
+    pub link_id_blacklist: Arc<HashSet<String>>,
+    pub started_at: Instant,
+    pub http_client: reqwest::Client,
+    pub signing_secret: Option<String>,
+    pub signed_url_ttl: Duration,
+    /// X-Robots-Tag header of redirects of links that have none of their own
+    pub redirect_robots_tag: Option<String>,
+    /// How long fetched previews of target pages are served before they are fetched again
+    pub preview_cache_ttl: Duration,
+    /// Manages the workspaces, nobody can while it is not set
+    pub super_admin_api_key: Option<String>,
+    pub config: Config,
+    pub maintenance_mode: MaintenanceMode,
+}
+
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for Config {
+    fn from_ref(state: &AppState) -> Self {
+        state.config
+    }
+}
+
+impl FromRef<AppState> for LinkCacheTtl {
+    fn from_ref(state: &AppState) -> Self {
+        state.link_cache_ttl.clone()
+    }
+}
+
+impl FromRef<AppState> for MaintenanceMode {
+    fn from_ref(state: &AppState) -> Self {
+        state.maintenance_mode.clone()
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TraceError;
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{self, Tracer};
+use opentelemetry_sdk::{runtime, Resource};
+use sentry::integrations::tracing::EventFilter;
+use sentry::types::{Dsn, ParseDsnError};
+use sentry::ClientInitGuard;
+use tracing::Metadata;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const API_KEY_HEADER: &str = "x-api-key";
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Exports all spans in batches to the OTLP collector at the given endpoint,
+/// e.g. Jaeger or Tempo, via gRPC.
+pub fn otlp_tracer(endpoint: &str) -> Result<Tracer, TraceError> {
+    // Without a propagator, the traceparent and tracestate headers are neither read nor written
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace::config().with_resource(Resource::new([
+            KeyValue::new("service.name", env!("CARGO_PKG_NAME")),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ])))
+        .install_batch(runtime::Tokio)
+}
+
+/// The trace context the caller sent as W3C `traceparent` and `tracestate` headers,
+/// so that the spans of a request continue its trace instead of starting a new one.
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// The W3C headers that let a downstream service, like the receiver of a webhook,
+/// join the trace of the current span.
+pub fn trace_headers() -> HashMap<String, String> {
+    let context = tracing::Span::current().context();
+    let mut headers = HashMap::new();
+
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+
+    // An empty tracestate is written even if the caller did not send one
+    headers.retain(|_, value| !value.is_empty());
+
+    headers
+}
+
+/// Reports errors to Sentry. The api key a request was authenticated with is scrubbed
+/// from everything that gets sent.
+pub fn init_sentry(dsn: &str, environment: &str) -> Result<ClientInitGuard, ParseDsnError> {
+    let options = sentry::ClientOptions {
+        dsn: Some(dsn.parse::<Dsn>()?),
+        release: sentry::release_name!(),
+        environment: Some(environment.to_owned().into()),
+        before_send: Some(Arc::new(|mut event| {
+            if let Some(request) = &mut event.request {
+                request.headers.retain(|name, _| !name.eq_ignore_ascii_case(API_KEY_HEADER));
+            }
+
+            Some(event)
+        })),
+        before_breadcrumb: Some(Arc::new(|mut breadcrumb| {
+            breadcrumb.data.retain(|name, _| !name.eq_ignore_ascii_case(API_KEY_HEADER));
+
+            Some(breadcrumb)
+        })),
+        ..Default::default()
+    };
+
+    Ok(sentry::init(options))
+}
+
+/// Turns logged errors into Sentry events and everything down to info into breadcrumbs.
+/// The trace layer logs every server error once more, which `report_server_errors` covers.
+pub fn sentry_event_filter(metadata: &Metadata) -> EventFilter {
+    match *metadata.level() {
+        tracing::Level::ERROR if !metadata.target().starts_with("tower_http") => EventFilter::Event,
+        tracing::Level::ERROR | tracing::Level::WARN | tracing::Level::INFO => EventFilter::Breadcrumb,
+        _ => EventFilter::Ignore,
+    }
+}
+
+/// Tags the Sentry events of a request with its request id and reports server errors
+/// no error was logged for, like timeouts, so that every one of them reaches Sentry.
+/// Does nothing unless Sentry is configured.
+pub async fn report_server_errors(req: Request, next: Next) -> Response {
+    if let Some(request_id) = req.headers().get("x-request-id").and_then(|value| value.to_str().ok()) {
+        sentry::configure_scope(|scope| scope.set_tag("request_id", request_id));
+    }
+
+    let response = next.run(req).await;
+
+    // Each request has a hub of its own, so this only knows about events of this request
+    if response.status().is_server_error() && sentry::last_event_id().is_none() {
+        sentry::capture_message(&format!("Responded with {}", response.status()), sentry::Level::Error);
+    }
+
+    response
+}
@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use axum::extract::Request;
+use axum::http::header::{HOST, LOCATION};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum_server::tls_rustls::RustlsConfig;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Reloads the certificate and key whenever one of them changes, so that renewed
+/// certificates are picked up without a restart. Like the blocklist, the parent
+/// directories are watched, because certificates are usually replaced instead of
+/// written to. Watching stops as soon as the returned watcher is dropped.
+pub fn watch(config: RustlsConfig, cert_path: &Path, key_path: &Path) -> notify::Result<RecommendedWatcher> {
+    let cert_path: PathBuf = cert_path.canonicalize()?;
+    let key_path: PathBuf = key_path.canonicalize()?;
+    let watched_paths = [cert_path.clone(), key_path.clone()];
+
+    // Events are delivered on a thread of the watcher, which is not part of the runtime
+    let runtime = tokio::runtime::Handle::current();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) if event.paths.iter().any(|changed| watched_paths.contains(changed)) => {
+                let config = config.clone();
+                let cert_path = watched_paths[0].clone();
+                let key_path = watched_paths[1].clone();
+
+                runtime.spawn(async move {
+                    match config.reload_from_pem_file(&cert_path, &key_path).await {
+                        Ok(()) => tracing::info!("Reloaded the TLS certificate from {}", cert_path.display()),
+                        // The previous certificate stays in use, e.g. while only one of both files got replaced
+                        Err(err) => tracing::error!("Reloading the TLS certificate failed: {}", err),
+                    }
+                });
+            }
+            Ok(_) => {}
+            Err(err) => tracing::error!("Watching the TLS certificate failed with the following error: {}", err),
+        }
+    })?;
+
+    for path in [&cert_path, &key_path] {
+        let directory = path.parent().unwrap_or(path);
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    }
+
+    Ok(watcher)
+}
+
+/// Answers every plain HTTP request with a permanent redirect to the same
+/// location on the HTTPS listener.
+pub async fn redirect_to_https(https_port: u16, req: Request) -> Response {
+    let Some(host) = req.headers().get(HOST).and_then(|value| value.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing host header").into_response();
+    };
+
+    // The port of the plain HTTP listener must not end up in the redirect
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    };
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+
+    let location = if https_port == 443 {
+        format!("https://{}{}", host, path_and_query)
+    } else {
+        format!("https://{}:{}{}", host, https_port, path_and_query)
+    };
+
+    // A 301 instead of the 308 of `Redirect::permanent`, which older clients do not know
+    (StatusCode::MOVED_PERMANENTLY, [(LOCATION, location)]).into_response()
+}
@@ -1,15 +1,191 @@
-use axum::http::StatusCode;
-use metrics::increment_counter;
+use std::future::Future;
+use std::time::Duration;
 
-pub fn internal_error<E>(err: E) -> (StatusCode, String)
+use axum::async_trait;
+use axum::extract::{FromRequest, Request};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use metrics::{histogram, increment_counter};
+use serde::de::DeserializeOwned;
+use tokio::time::error::Elapsed;
+use tokio::time::Instant;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::{AppError, ErrorBody, FieldError};
+
+/// Coarse category of an internal error. Only these end up as label values, because
+/// labeling with the error message would create a new time series for every message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    DbTimeout,
+    DbError,
+    IoError,
+    Unknown,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::DbTimeout => "db_timeout",
+            ErrorKind::DbError => "db_error",
+            ErrorKind::IoError => "io_error",
+            ErrorKind::Unknown => "unknown",
+        }
+    }
+
+    fn of(err: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(err) = err.downcast_ref::<sqlx::Error>() {
+            ErrorKind::from(err)
+        } else if err.is::<Elapsed>() {
+            // Every timeout of a request is one of a database query, see `timed_query`
+            ErrorKind::DbTimeout
+        } else if err.is::<std::io::Error>() {
+            ErrorKind::IoError
+        } else {
+            ErrorKind::Unknown
+        }
+    }
+}
+
+impl From<&sqlx::Error> for ErrorKind {
+    fn from(err: &sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::PoolTimedOut => ErrorKind::DbTimeout,
+            sqlx::Error::Io(_) => ErrorKind::IoError,
+            _ => ErrorKind::DbError,
+        }
+    }
+}
+
+impl From<sqlx::Error> for ErrorKind {
+    fn from(err: sqlx::Error) -> Self {
+        ErrorKind::from(&err)
+    }
+}
+
+pub fn internal_error<E>(err: E) -> AppError
 where
-    E: std::error::Error,
+    E: std::error::Error + 'static,
 {
     tracing::error!("{}", err);
 
-    let labels = [("error", format!("{}!", err))];
+    let labels = [("error_kind", ErrorKind::of(&err).as_str())];
 
     increment_counter!("request_error", &labels);
 
-    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    // All connections being busy is temporary, so clients are told to retry instead of giving up
+    let is_pool_exhausted = (&err as &(dyn std::error::Error + 'static))
+        .downcast_ref::<sqlx::Error>()
+        .is_some_and(|err| matches!(err, sqlx::Error::PoolTimedOut));
+
+    if is_pool_exhausted {
+        increment_counter!("pool_exhaustion_total");
+
+        return AppError::PoolExhausted(ErrorBody::new(
+            "service_busy",
+            "All database connections are busy, retry shortly",
+        ));
+    }
+
+    AppError::Internal(ErrorBody::new("internal_error", err.to_string()))
+}
+
+/// Runs a database query with a timeout and records how long it took as the
+/// `db_query_duration_seconds` histogram. Queries that time out are recorded as well,
+/// so that they show up in the upper percentiles.
+pub async fn timed_query<F: Future>(
+    query: &'static str,
+    timeout: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let started_at = Instant::now();
+    let output = tokio::time::timeout(timeout, future).await;
+
+    histogram!("db_query_duration_seconds", started_at.elapsed().as_secs_f64(), "query" => query);
+
+    output
+}
+
+/// Fields of bodies are declared in snake case, but sent camel cased like the rest of the api
+fn camel_case(field: &str) -> String {
+    let mut words = field.split('_');
+    let mut camel_cased = words.next().unwrap_or_default().to_owned();
+
+    for word in words {
+        let mut chars = word.chars();
+
+        if let Some(first) = chars.next() {
+            camel_cased.extend(first.to_uppercase());
+            camel_cased.push_str(chars.as_str());
+        }
+    }
+
+    camel_cased
+}
+
+/// Turns the failed constraints of a request into a 422 that lists them field by field,
+/// named like the client sent them. The message sums them up, so clients that only
+/// show it still tell what is wrong.
+pub(crate) fn validation_error(
+    code: &'static str,
+    errors: ValidationErrors,
+    field_name: fn(&str) -> String,
+) -> AppError {
+    let mut fields: Vec<FieldError> = errors
+        .into_errors()
+        .into_iter()
+        .filter_map(|(field, kind)| match kind {
+            ValidationErrorsKind::Field(errors) => Some((field, errors)),
+            // None of the requests nest validated structs or lists
+            ValidationErrorsKind::Struct(_) | ValidationErrorsKind::List(_) => None,
+        })
+        .flat_map(|(field, errors)| {
+            errors.into_iter().map(move |error| FieldError {
+                field: field_name(field),
+                message: error.message.as_deref().unwrap_or(&error.code).to_owned(),
+                code: error.code.into_owned(),
+            })
+        })
+        .collect();
+
+    // The errors come out of a hash map, sorting them keeps responses stable
+    fields.sort_by(|a, b| a.field.cmp(&b.field).then_with(|| a.code.cmp(&b.code)));
+
+    let message = fields
+        .iter()
+        .map(|field| field.message.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    AppError::UnprocessableEntity(ErrorBody::new(code, message).with_fields(fields))
+}
+
+pub(crate) fn body_validation_error(errors: ValidationErrors) -> AppError {
+    validation_error("validation_failed", errors, camel_case)
+}
+
+/// Extracts a json body like `Json` and checks the constraints its type declares with
+/// `#[validate]`, so that handlers only ever see bodies that satisfy them. Checks that
+/// need the state, like the blocklist, are still up to the handlers.
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        value
+            .validate()
+            .map_err(|errors| body_validation_error(errors).into_response())?;
+
+        Ok(Self(value))
+    }
 }
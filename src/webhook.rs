@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use metrics::increment_counter;
+use reqwest::Client;
+
+use crate::telemetry::trace_headers;
+
+const MAX_DELIVERY_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClickEvent {
+    pub link_id: String,
+    pub target_url: String,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub clicked_at: DateTime<Utc>,
+}
+
+async fn deliver(
+    client: &Client,
+    webhook_url: &str,
+    event: &ClickEvent,
+    trace_headers: &HashMap<String, String>,
+) -> reqwest::Result<()> {
+    let mut request = client
+        .post(webhook_url)
+        .timeout(DELIVERY_TIMEOUT)
+        .json(event);
+
+    for (name, value) in trace_headers {
+        request = request.header(name, value);
+    }
+
+    request
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Sends the click event to the webhook in the background, so that redirects never
+/// wait for third parties. Failed deliveries are retried with exponential backoff.
+pub fn notify_click(client: Client, webhook_url: String, event: ClickEvent) {
+    // Taken before spawning, as the task no longer runs within the span of the redirect
+    let trace_headers = trace_headers();
+
+    tokio::spawn(async move {
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+            match deliver(&client, &webhook_url, &event, &trace_headers).await {
+                Ok(()) => {
+                    tracing::debug!("Delivered click webhook for link with id {}", event.link_id);
+
+                    return;
+                }
+                Err(err) if attempt < MAX_DELIVERY_ATTEMPTS => {
+                    tracing::warn!(
+                        "Delivering click webhook for link with id {} failed in attempt {}, retrying: {}",
+                        event.link_id,
+                        attempt,
+                        err
+                    );
+
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "Delivering click webhook for link with id {} failed after {} attempts: {}",
+                        event.link_id,
+                        MAX_DELIVERY_ATTEMPTS,
+                        err
+                    );
+                    increment_counter!("webhook_delivery_failures_total");
+                }
+            }
+        }
+    });
+}